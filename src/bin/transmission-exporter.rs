@@ -0,0 +1,72 @@
+//! Prometheus exporter for a Transmission daemon.
+//!
+//! The daemon is found the same way as by the `transmission-rpc` command line tool.
+
+use std::net::SocketAddr;
+use std::process;
+
+use clap::Parser;
+use transmission_rpc::config::ConnectionArgs;
+use transmission_rpc::exporter::{self, ExporterConfig};
+use transmission_rpc::filter::TorrentFilter;
+use transmission_rpc::types::Result;
+use transmission_rpc::RetryPolicy;
+
+#[derive(Parser, Debug)]
+#[command(name = "transmission-exporter", version, about = "Prometheus exporter for a Transmission daemon")]
+struct Cli {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+    /// address to serve the metrics on
+    #[arg(short, long, default_value = "127.0.0.1:9190")]
+    listen: SocketAddr,
+    /// do not publish per-torrent series
+    #[arg(long)]
+    no_per_torrent: bool,
+    /// at most this many torrents get per-torrent series, 0 for no limit
+    #[arg(long, default_value = "100")]
+    max_torrents: usize,
+    /// only torrents with one of these labels get per-torrent series, can be repeated
+    #[arg(long = "label")]
+    labels: Vec<String>,
+    /// identify torrents by hash only, without a name label
+    #[arg(long)]
+    no_torrent_names: bool,
+    /// do not publish announce results by tracker host
+    #[arg(long)]
+    no_trackers: bool,
+    /// do not publish the free space of download dirs
+    #[arg(long)]
+    no_free_space: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let mut client = cli.connection.client()?;
+    // the next scrape is the retry, backing off would only make the scrape time out
+    client.set_retry_policy(RetryPolicy::none());
+
+    let exporter_config = ExporterConfig {
+        per_torrent: !cli.no_per_torrent,
+        torrent_filter: if cli.labels.is_empty() {
+            None
+        } else {
+            Some(TorrentFilter::Any(cli.labels.into_iter().map(TorrentFilter::Label).collect()))
+        },
+        max_torrents: if cli.max_torrents == 0 { None } else { Some(cli.max_torrents) },
+        torrent_name_label: !cli.no_torrent_names,
+        trackers: !cli.no_trackers,
+        free_space: !cli.no_free_space,
+    };
+    exporter::serve(cli.listen, client, exporter_config).await
+}
@@ -0,0 +1,553 @@
+//! Command line client of the Transmission RPC.
+//!
+//! The daemon is found as described in `transmission_rpc::config::ConnectionArgs`.
+
+mod output;
+
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use base64::Engine;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use transmission_rpc::types::{Id, Result};
+use transmission_rpc::types::{SessionSetArgs, Torrent, TorrentAction, TorrentAddArgs, TorrentGetField};
+use transmission_rpc::types::{TorrentSetArgs, TrackerList};
+
+use transmission_rpc::config::ConnectionArgs;
+use output::{Format, Table};
+
+/// Evaluates to the response, or returns its result as error if the daemon did not succeed
+macro_rules! checked {
+    ($response:expr) => {{
+        let response = $response;
+        if !response.is_ok() {
+            return Err(response.result.into());
+        }
+        response
+    }};
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "transmission-rpc", version, about = "Command line client of the Transmission RPC")]
+struct Cli {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+    #[arg(short, long, value_enum, default_value = "table", global = true)]
+    output: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List torrents
+    List {
+        /// torrent ids or hashes, all torrents if none
+        ids: Vec<String>,
+    },
+    /// Add torrents from .torrent files, URLs or magnet links
+    Add {
+        #[arg(required = true)]
+        sources: Vec<String>,
+        #[arg(short, long)]
+        download_dir: Option<String>,
+        /// add without starting
+        #[arg(long)]
+        paused: bool,
+        /// label of the added torrents, can be repeated
+        #[arg(short, long = "label")]
+        labels: Vec<String>,
+    },
+    /// Remove torrents
+    Remove {
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// also delete the downloaded data
+        #[arg(long)]
+        delete_data: bool,
+    },
+    /// Start torrents
+    Start {
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// bypass the download queue
+        #[arg(long)]
+        now: bool,
+    },
+    /// Stop torrents
+    Stop {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Verify the downloaded data of torrents
+    Verify {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Ask the trackers for more peers
+    Reannounce {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Move the data of torrents to a new location
+    Move {
+        location: String,
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// only tell the daemon to look for the data at the new location
+        #[arg(long)]
+        no_move: bool,
+    },
+    /// Rename a file or directory of a torrent
+    Rename {
+        id: String,
+        /// current path of the file or directory inside the torrent
+        path: String,
+        /// new name
+        name: String,
+    },
+    /// Change labels and trackers of torrents in one call
+    Set(TorrentSet),
+    /// Set, add or remove labels of torrents
+    Labels {
+        #[arg(value_enum)]
+        action: LabelAction,
+        /// comma separated torrent ids or hashes
+        ids: String,
+        labels: Vec<String>,
+    },
+    /// Edit trackers of torrents
+    #[command(subcommand)]
+    Trackers(TrackersCommand),
+    /// Session settings
+    #[command(subcommand)]
+    Session(SessionCommand),
+    /// Session statistics
+    Stats,
+    /// Free space in a directory of the daemon
+    FreeSpace { path: String },
+    /// Check whether the peer port is reachable
+    PortTest,
+    /// Update the blocklist from its url
+    BlocklistUpdate,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LabelAction {
+    Set,
+    Add,
+    Remove,
+}
+
+#[derive(Subcommand, Debug)]
+enum TrackersCommand {
+    /// Add announce URLs
+    Add {
+        /// comma separated torrent ids or hashes
+        ids: String,
+        #[arg(required = true)]
+        urls: Vec<String>,
+        /// tier to add the URLs to, starting from 0, a new tier if not given
+        #[arg(long)]
+        tier: Option<usize>,
+    },
+    /// Remove a tracker by its id
+    Remove {
+        /// comma separated torrent ids or hashes
+        ids: String,
+        tracker_id: i64,
+    },
+    /// Replace an announce URL in all torrents
+    Replace { old_url: String, new_url: String },
+    /// Replace all trackers of torrents
+    Set {
+        /// comma separated torrent ids or hashes
+        ids: String,
+        /// one tier per argument, with its announce URLs comma separated
+        #[arg(required = true)]
+        tiers: Vec<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct TorrentSet {
+    /// comma separated torrent ids or hashes
+    ids: String,
+    /// label replacing all labels of the torrents, can be repeated
+    #[arg(short, long = "label")]
+    labels: Vec<String>,
+    /// remove all labels of the torrents
+    #[arg(long, conflicts_with = "labels")]
+    clear_labels: bool,
+    /// announce URL to add in a new tier, can be repeated
+    #[arg(long)]
+    tracker_add: Vec<String>,
+    /// id of a tracker to remove, can be repeated
+    #[arg(long)]
+    tracker_remove: Vec<i64>,
+    /// new announce URL of a tracker as ID=URL, can be repeated
+    #[arg(long, value_parser = parse_tracker_replace)]
+    tracker_replace: Vec<(i64, String)>,
+}
+
+impl From<TorrentSet> for TorrentSetArgs {
+    fn from(set: TorrentSet) -> Self {
+        fn non_empty<T>(list: Vec<T>) -> Option<Vec<T>> {
+            if list.is_empty() { None } else { Some(list) }
+        }
+        TorrentSetArgs {
+            labels: if set.clear_labels { Some(vec![]) } else { non_empty(set.labels) },
+            tracker_add: non_empty(set.tracker_add),
+            tracker_remove: non_empty(set.tracker_remove),
+            tracker_replace: non_empty(set.tracker_replace),
+            tracker_list: None,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum SessionCommand {
+    /// Show session settings
+    Get,
+    /// Change session settings
+    Set(SessionSet),
+    /// Shut the daemon down
+    Close,
+}
+
+#[derive(Args, Debug)]
+struct SessionSet {
+    #[arg(long)]
+    alt_speed_enabled: Option<bool>,
+    /// alternative download limit, in KBps
+    #[arg(long)]
+    alt_speed_down: Option<i64>,
+    /// alternative upload limit, in KBps
+    #[arg(long)]
+    alt_speed_up: Option<i64>,
+    #[arg(long)]
+    blocklist_enabled: Option<bool>,
+    #[arg(long)]
+    blocklist_url: Option<String>,
+    #[arg(long)]
+    download_dir: Option<String>,
+    /// required, preferred or tolerated
+    #[arg(long)]
+    encryption: Option<String>,
+    #[arg(long)]
+    peer_port: Option<i64>,
+    /// download limit, in KBps
+    #[arg(long)]
+    speed_limit_down: Option<i64>,
+    #[arg(long)]
+    speed_limit_down_enabled: Option<bool>,
+    /// upload limit, in KBps
+    #[arg(long)]
+    speed_limit_up: Option<i64>,
+    #[arg(long)]
+    speed_limit_up_enabled: Option<bool>,
+}
+
+impl From<SessionSet> for SessionSetArgs {
+    fn from(set: SessionSet) -> Self {
+        SessionSetArgs {
+            alt_speed_down: set.alt_speed_down,
+            alt_speed_enabled: set.alt_speed_enabled,
+            alt_speed_up: set.alt_speed_up,
+            blocklist_enabled: set.blocklist_enabled,
+            blocklist_url: set.blocklist_url,
+            download_dir: set.download_dir,
+            encryption: set.encryption,
+            peer_port: set.peer_port,
+            speed_limit_down: set.speed_limit_down,
+            speed_limit_down_enabled: set.speed_limit_down_enabled,
+            speed_limit_up: set.speed_limit_up,
+            speed_limit_up_enabled: set.speed_limit_up_enabled,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let mut client = cli.connection.client()?;
+    let format = cli.output;
+
+    match cli.command {
+        Command::List { ids } => {
+            let ids = if ids.is_empty() { None } else { Some(parse_ids(&ids)?) };
+            let fields = vec![
+                TorrentGetField::Id,
+                TorrentGetField::Name,
+                TorrentGetField::Status,
+                TorrentGetField::Percentdone,
+                TorrentGetField::Totalsize,
+                TorrentGetField::Ratedownload,
+                TorrentGetField::Rateupload,
+                TorrentGetField::Uploadratio,
+                TorrentGetField::Eta,
+                TorrentGetField::HashString,
+            ];
+            let response = checked!(client.torrent_get(Some(fields), ids).await?);
+            let mut table = Table::new(&["id", "name", "status", "done", "size", "down", "up", "ratio", "eta", "hash"]);
+            for torrent in &response.arguments.torrents {
+                table.push(torrent_row(torrent));
+            }
+            print!("{}", table.render(format));
+        }
+        Command::Add {
+            sources,
+            download_dir,
+            paused,
+            labels,
+        } => {
+            let mut table = Table::new(&["id", "name", "hash"]);
+            for source in sources {
+                let mut add = source_args(&source)?;
+                add.download_dir = download_dir.clone();
+                add.paused = if paused { Some(true) } else { None };
+                add.labels = if labels.is_empty() { None } else { Some(labels.clone()) };
+                let response = checked!(client.torrent_add(add).await?);
+                if let Some(torrent) = response.arguments.torrent_added {
+                    table.push(vec![json!(torrent.id), json!(torrent.name), json!(torrent.hash_string)]);
+                }
+            }
+            print!("{}", table.render(format));
+        }
+        Command::Remove { ids, delete_data } => {
+            checked!(client.torrent_remove(parse_ids(&ids)?, delete_data).await?);
+        }
+        Command::Start { ids, now } => {
+            let action = if now { TorrentAction::StartNow } else { TorrentAction::Start };
+            checked!(client.torrent_action(action, parse_ids(&ids)?).await?);
+        }
+        Command::Stop { ids } => {
+            checked!(client.torrent_action(TorrentAction::Stop, parse_ids(&ids)?).await?);
+        }
+        Command::Verify { ids } => {
+            checked!(client.torrent_action(TorrentAction::Verify, parse_ids(&ids)?).await?);
+        }
+        Command::Reannounce { ids } => {
+            checked!(client.torrent_action(TorrentAction::Reannounce, parse_ids(&ids)?).await?);
+        }
+        Command::Move { location, ids, no_move } => {
+            checked!(client.torrent_set_location(parse_ids(&ids)?, location, Some(!no_move)).await?);
+        }
+        Command::Rename { id, path, name } => {
+            let response = checked!(client.torrent_rename_path(parse_ids(&[id])?, path, name).await?);
+            let renamed = response.arguments;
+            let record = Table::record(vec![
+                ("id", json!(renamed.id)),
+                ("path", json!(renamed.path)),
+                ("name", json!(renamed.name)),
+            ]);
+            print!("{}", record.render(format));
+        }
+        Command::Set(set) => {
+            let ids = parse_ids(std::slice::from_ref(&set.ids))?;
+            let args = TorrentSetArgs::from(set);
+            if args.labels.is_none()
+                && args.tracker_add.is_none()
+                && args.tracker_remove.is_none()
+                && args.tracker_replace.is_none()
+            {
+                return Err("Nothing to set".into());
+            }
+            checked!(client.torrent_set(args, Some(ids)).await?);
+        }
+        Command::Labels { action, ids, labels } => {
+            let ids = parse_ids(&[ids])?;
+            let response = match action {
+                LabelAction::Set => client.set_labels(ids, labels).await?,
+                LabelAction::Add => client.add_labels(ids, labels).await?,
+                LabelAction::Remove => client.remove_labels(ids, labels).await?,
+            };
+            checked!(response);
+        }
+        Command::Trackers(command) => {
+            let response = match command {
+                TrackersCommand::Add { ids, urls, tier } => client.add_trackers(parse_ids(&[ids])?, tier, urls).await?,
+                TrackersCommand::Remove { ids, tracker_id } => {
+                    client.remove_tracker(parse_ids(&[ids])?, tracker_id).await?
+                }
+                TrackersCommand::Replace { old_url, new_url } => client.replace_tracker_url(&old_url, &new_url).await?,
+                TrackersCommand::Set { ids, tiers } => {
+                    let tiers = tiers
+                        .iter()
+                        .map(|tier| tier.split(',').map(str::trim).filter(|it| !it.is_empty()).map(String::from).collect())
+                        .collect();
+                    client.set_tracker_list(parse_ids(&[ids])?, TrackerList { tiers }).await?
+                }
+            };
+            checked!(response);
+        }
+        Command::Session(SessionCommand::Get) => {
+            let session = checked!(client.session_get().await?).arguments;
+            let record = Table::record(vec![
+                ("version", json!(session.version)),
+                ("rpc-version", json!(session.rpc_version)),
+                ("rpc-version-minimum", json!(session.rpc_version_minimum)),
+                ("download-dir", json!(session.download_dir)),
+                ("encryption", json!(session.encryption)),
+                ("blocklist-enabled", json!(session.blocklist_enabled)),
+                ("alt-speed-enabled", json!(session.alt_speed_enabled)),
+                ("alt-speed-down", json!(session.alt_speed_down)),
+                ("alt-speed-up", json!(session.alt_speed_up)),
+            ]);
+            print!("{}", record.render(format));
+        }
+        Command::Session(SessionCommand::Set(set)) => {
+            checked!(client.session_set(set.into()).await?);
+        }
+        Command::Session(SessionCommand::Close) => {
+            checked!(client.session_close().await?);
+        }
+        Command::Stats => {
+            let stats = checked!(client.session_stats().await?).arguments;
+            let record = Table::record(vec![
+                ("torrents", json!(stats.torrent_count)),
+                ("active", json!(stats.active_torrent_count)),
+                ("paused", json!(stats.paused_torrent_count)),
+                ("download-speed", json!(stats.download_speed)),
+                ("upload-speed", json!(stats.upload_speed)),
+                ("downloaded", json!(stats.current_stats.downloaded_bytes)),
+                ("uploaded", json!(stats.current_stats.uploaded_bytes)),
+                ("seconds-active", json!(stats.current_stats.seconds_active)),
+                ("total-downloaded", json!(stats.cumulative_stats.downloaded_bytes)),
+                ("total-uploaded", json!(stats.cumulative_stats.uploaded_bytes)),
+                ("total-seconds-active", json!(stats.cumulative_stats.seconds_active)),
+            ]);
+            print!("{}", record.render(format));
+        }
+        Command::FreeSpace { path } => {
+            let free_space = checked!(client.free_space(path).await?).arguments;
+            let record = Table::record(vec![("path", json!(free_space.path)), ("size-bytes", json!(free_space.size_bytes))]);
+            print!("{}", record.render(format));
+        }
+        Command::PortTest => {
+            let port_test = checked!(client.port_test().await?).arguments;
+            print!("{}", Table::record(vec![("port-is-open", json!(port_test.port_is_open))]).render(format));
+        }
+        Command::BlocklistUpdate => {
+            let update = checked!(client.blocklist_update().await?).arguments;
+            print!("{}", Table::record(vec![("blocklist-size", json!(update.blocklist_size))]).render(format));
+        }
+    }
+    Ok(())
+}
+
+/// Parses ids given as separate arguments or comma separated, e.g. `1,4-6 a1b2...`.
+/// Numbers and ranges of numbers are torrent ids, anything else a hash.
+fn parse_ids(args: &[String]) -> Result<Vec<Id>> {
+    let mut ids = vec![];
+    for arg in args.iter().flat_map(|it| it.split(',')).map(str::trim).filter(|it| !it.is_empty()) {
+        if let Ok(id) = arg.parse::<i64>() {
+            ids.push(Id::Id(id));
+        } else if let Some((first, last)) = arg.split_once('-').and_then(|(a, b)| Some((a.parse::<i64>().ok()?, b.parse::<i64>().ok()?))) {
+            if first > last {
+                return Err(format!("Invalid id range {}", arg).into());
+            }
+            ids.extend((first..=last).map(Id::Id));
+        } else if arg.chars().all(|c| c.is_ascii_hexdigit()) {
+            ids.push(Id::Hash(arg.to_lowercase()));
+        } else {
+            return Err(format!("Invalid torrent id {}", arg).into());
+        }
+    }
+    if ids.is_empty() {
+        return Err("No torrent id given".into());
+    }
+    Ok(ids)
+}
+
+/// Parses `ID=URL` of a tracker to replace
+fn parse_tracker_replace(arg: &str) -> std::result::Result<(i64, String), String> {
+    let (id, url) = arg.split_once('=').ok_or_else(|| format!("Expected ID=URL, got {}", arg))?;
+    let id = id.trim().parse().map_err(|_| format!("Invalid tracker id {}", id))?;
+    Ok((id, url.trim().to_string()))
+}
+
+/// Add arguments for a source: URLs and magnet links are passed to the daemon,
+/// local .torrent files are sent as metainfo
+fn source_args(source: &str) -> Result<TorrentAddArgs> {
+    let is_remote = source.starts_with("magnet:") || source.contains("://");
+    if is_remote || !Path::new(source).exists() {
+        return Ok(TorrentAddArgs {
+            filename: Some(source.to_string()),
+            ..TorrentAddArgs::default()
+        });
+    }
+    let content = fs::read(source).map_err(|e| format!("Unable to read {}: {}", source, e))?;
+    Ok(TorrentAddArgs {
+        metainfo: Some(base64::engine::general_purpose::STANDARD.encode(content)),
+        ..TorrentAddArgs::default()
+    })
+}
+
+fn torrent_row(torrent: &Torrent) -> Vec<Value> {
+    vec![
+        json!(torrent.id),
+        json!(torrent.name),
+        json!(torrent.torrent_status().map(|it| it.to_str())),
+        json!(torrent.percent_done.map(|it| (it as f64 * 1000.0).round() / 10.0)),
+        json!(torrent.total_size),
+        json!(torrent.rate_download),
+        json!(torrent.rate_upload),
+        json!(torrent.upload_ratio.map(|it| (it as f64 * 100.0).round() / 100.0)),
+        json!(torrent.eta),
+        json!(torrent.hash_string),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_ids() -> Result<()> {
+        let ids = parse_ids(&["1,3-5".to_string(), "ABCDEF".to_string()])?;
+        assert_eq!(
+            ids,
+            vec![Id::Id(1), Id::Id(3), Id::Id(4), Id::Id(5), Id::Hash("abcdef".to_string())]
+        );
+        assert!(parse_ids(&["5-3".to_string()]).is_err());
+        assert!(parse_ids(&["foo".to_string()]).is_err());
+        assert!(parse_ids(&[",".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_torrent_set() {
+        let cli = Cli::try_parse_from([
+            "transmission-rpc", "set", "1,2", "-l", "tv", "--tracker-remove", "3", "--tracker-replace", "4=udp://new",
+        ])
+        .unwrap();
+        let set = match cli.command {
+            Command::Set(set) => set,
+            command => panic!("unexpected {:?}", command),
+        };
+        let args = TorrentSetArgs::from(set);
+        assert_eq!(args.labels, Some(vec!["tv".to_string()]));
+        assert_eq!(args.tracker_add, None);
+        assert_eq!(args.tracker_remove, Some(vec![3]));
+        assert_eq!(args.tracker_replace, Some(vec![(4, "udp://new".to_string())]));
+        assert!(parse_tracker_replace("udp://new").is_err());
+    }
+
+    #[test]
+    pub fn test_cli_definition() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+}
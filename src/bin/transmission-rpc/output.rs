@@ -0,0 +1,181 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Rows of values printed the same way whatever the output format.
+/// A record is a table of a single row, printed as key and value lines in table format
+/// and as a single object in JSON.
+#[derive(Debug, Clone)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    record: bool,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        Table {
+            headers: headers.iter().map(|it| it.to_string()).collect(),
+            rows: vec![],
+            record: false,
+        }
+    }
+
+    pub fn record(fields: Vec<(&str, Value)>) -> Table {
+        let (headers, row): (Vec<&str>, Vec<Value>) = fields.into_iter().unzip();
+        let mut table = Table::new(&headers);
+        table.rows.push(row);
+        table.record = true;
+        table
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        self.rows.push(row);
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Table if self.record => self.render_record(),
+            Format::Table => self.render_table(),
+            Format::Json => self.render_json(),
+            Format::Csv => self.render_csv(),
+        }
+    }
+
+    fn render_record(&self) -> String {
+        let width = self.headers.iter().map(|it| it.chars().count()).max().unwrap_or(0);
+        let mut out = String::new();
+        for (header, value) in self.headers.iter().zip(&self.rows[0]) {
+            out.push_str(&format!("{:width$}  {}\n", header, text(value), width = width));
+        }
+        out
+    }
+
+    fn render_table(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|it| it.chars().count()).collect();
+        for row in &self.rows {
+            for (width, value) in widths.iter_mut().zip(row) {
+                *width = (*width).max(text(value).chars().count());
+            }
+        }
+        let mut out = String::new();
+        let mut line = |cells: Vec<(String, bool)>| {
+            let cells: Vec<String> = cells
+                .into_iter()
+                .zip(&widths)
+                .map(|((cell, right), width)| {
+                    if right {
+                        format!("{:>width$}", cell, width = width)
+                    } else {
+                        format!("{:width$}", cell, width = width)
+                    }
+                })
+                .collect();
+            out.push_str(cells.join("  ").trim_end());
+            out.push('\n');
+        };
+        line(self.headers.iter().map(|it| (it.to_uppercase(), false)).collect());
+        for row in &self.rows {
+            line(row.iter().map(|it| (text(it), it.is_number())).collect());
+        }
+        out
+    }
+
+    fn render_json(&self) -> String {
+        let objects: Vec<String> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let fields: Vec<String> = self
+                    .headers
+                    .iter()
+                    .zip(row)
+                    .map(|(header, value)| format!("{}: {}", Value::from(header.as_str()), value))
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            })
+            .collect();
+        if self.record {
+            format!("{}\n", objects[0])
+        } else if objects.is_empty() {
+            "[]\n".to_string()
+        } else {
+            format!("[\n  {}\n]\n", objects.join(",\n  "))
+        }
+    }
+
+    fn render_csv(&self) -> String {
+        let mut out = String::new();
+        let headers: Vec<String> = self.headers.iter().map(|it| csv_field(it)).collect();
+        out.push_str(&headers.join(","));
+        out.push('\n');
+        for row in &self.rows {
+            let cells: Vec<String> = row.iter().map(|it| csv_field(&text(it))).collect();
+            out.push_str(&cells.join(","));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// A value as shown in table and CSV output, strings without quotes and null as empty
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values.iter().map(text).collect::<Vec<String>>().join(" "),
+        other => other.to_string(),
+    }
+}
+
+/// Quotes a CSV field if needed, as described in RFC 4180
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table() -> Table {
+        let mut table = Table::new(&["id", "name", "ratio"]);
+        table.push(vec![json!(1), json!("Ubuntu, \"LTS\""), json!(1.5)]);
+        table.push(vec![json!(12), json!("Debian"), Value::Null]);
+        table
+    }
+
+    #[test]
+    pub fn test_render_table() {
+        assert_eq!(
+            table().render(Format::Table),
+            "ID  NAME           RATIO\n 1  Ubuntu, \"LTS\"    1.5\n12  Debian\n"
+        );
+        let record = Table::record(vec![("version", json!("4.0.5")), ("rpc-version", json!(17))]);
+        assert_eq!(record.render(Format::Table), "version      4.0.5\nrpc-version  17\n");
+        assert_eq!(record.render(Format::Json), "{\"version\": \"4.0.5\", \"rpc-version\": 17}\n");
+    }
+
+    #[test]
+    pub fn test_render_json_and_csv() {
+        assert_eq!(
+            table().render(Format::Json),
+            "[\n  {\"id\": 1, \"name\": \"Ubuntu, \\\"LTS\\\"\", \"ratio\": 1.5},\n  {\"id\": 12, \"name\": \"Debian\", \"ratio\": null}\n]\n"
+        );
+        assert_eq!(
+            table().render(Format::Csv),
+            "id,name,ratio\n1,\"Ubuntu, \"\"LTS\"\"\",1.5\n12,Debian,\n"
+        );
+        assert_eq!(Table::new(&["id"]).render(Format::Json), "[]\n");
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::VecDeque;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use transmission_rpc::filter::{SortKey, TorrentFilter, TorrentQuery};
+use transmission_rpc::types::{Id, Torrent, TorrentAction, TorrentStatus};
+
+/// Number of speed samples kept for the graph
+pub const HISTORY: usize = 300;
+
+pub const SORT_KEYS: [SortKey; 7] = [
+    SortKey::QueuePosition,
+    SortKey::Name,
+    SortKey::PercentDone,
+    SortKey::RateDownload,
+    SortKey::RateUpload,
+    SortKey::Ratio,
+    SortKey::TotalSize,
+];
+
+/// Torrents shown in the list, cycled with `f`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    All,
+    Downloading,
+    Seeding,
+    Stopped,
+    Checking,
+    Errored,
+    Stalled,
+}
+
+impl View {
+    const ALL: [View; 7] = [
+        View::All,
+        View::Downloading,
+        View::Seeding,
+        View::Stopped,
+        View::Checking,
+        View::Errored,
+        View::Stalled,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            View::All => "all",
+            View::Downloading => "downloading",
+            View::Seeding => "seeding",
+            View::Stopped => "stopped",
+            View::Checking => "checking",
+            View::Errored => "errored",
+            View::Stalled => "stalled",
+        }
+    }
+
+    fn filter(&self) -> Option<TorrentFilter> {
+        let status = TorrentFilter::Status;
+        match self {
+            View::All => None,
+            View::Downloading => Some(status(TorrentStatus::Downloading).or(status(TorrentStatus::QueuedToDownload))),
+            View::Seeding => Some(status(TorrentStatus::Seeding).or(status(TorrentStatus::QueuedToSeed))),
+            View::Stopped => Some(status(TorrentStatus::Stopped)),
+            View::Checking => Some(status(TorrentStatus::Verifying).or(status(TorrentStatus::QueuedToVerify))),
+            View::Errored => Some(TorrentFilter::HasError),
+            View::Stalled => Some(TorrentFilter::IsStalled),
+        }
+    }
+}
+
+/// Tabs of the detail pane, cycled with `Tab`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tab {
+    Peers,
+    Trackers,
+    Files,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 3] = [Tab::Peers, Tab::Trackers, Tab::Files];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tab::Peers => "Peers",
+            Tab::Trackers => "Trackers",
+            Tab::Files => "Files",
+        }
+    }
+}
+
+/// What keys currently do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    Normal,
+    /// typing the name filter
+    Filter,
+    /// waiting for `y` to remove the torrent, with its data if true
+    ConfirmRemove(bool),
+}
+
+/// A call to make to the daemon, asked for by a key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Action(TorrentAction, Id),
+    Remove { id: Id, delete_local_data: bool },
+}
+
+#[derive(Debug)]
+pub struct App {
+    /// torrents of the last refresh, unsorted
+    pub torrents: Vec<Torrent>,
+    /// peers, trackers and files of the selected torrent
+    pub detail: Option<Torrent>,
+    pub sort: usize,
+    pub descending: bool,
+    pub view: View,
+    pub name_filter: String,
+    pub mode: Mode,
+    /// index of the selected torrent among the visible ones
+    pub selected: usize,
+    pub tab: Tab,
+    /// session download and upload speeds, oldest first
+    pub download_history: VecDeque<u64>,
+    pub upload_history: VecDeque<u64>,
+    /// last error or confirmation to show in the status bar
+    pub message: Option<String>,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new() -> App {
+        App {
+            torrents: vec![],
+            detail: None,
+            sort: 0,
+            descending: false,
+            view: View::All,
+            name_filter: String::new(),
+            mode: Mode::Normal,
+            selected: 0,
+            tab: Tab::Peers,
+            download_history: VecDeque::with_capacity(HISTORY),
+            upload_history: VecDeque::with_capacity(HISTORY),
+            message: None,
+            quit: false,
+        }
+    }
+
+    pub fn sort_key(&self) -> SortKey {
+        SORT_KEYS[self.sort]
+    }
+
+    fn query(&self) -> TorrentQuery {
+        let mut query = TorrentQuery::new().sort_by(self.sort_key(), self.descending);
+        if let Some(filter) = self.view.filter() {
+            query = query.filter(filter);
+        }
+        if !self.name_filter.is_empty() {
+            query = query.filter(TorrentFilter::NameContains(self.name_filter.clone()));
+        }
+        query.sort_by(SortKey::Id, false)
+    }
+
+    /// Torrents of the list, filtered and sorted
+    pub fn visible(&self) -> Vec<Torrent> {
+        self.query().apply(self.torrents.clone())
+    }
+
+    pub fn selected_torrent(&self) -> Option<Torrent> {
+        self.visible().into_iter().nth(self.selected)
+    }
+
+    /// Replaces the torrents, keeping the same torrent selected if it is still visible
+    pub fn set_torrents(&mut self, torrents: Vec<Torrent>) {
+        let selected_id = self.selected_torrent().and_then(|it| it.id);
+        self.torrents = torrents;
+        let visible = self.visible();
+        if let Some(index) = visible.iter().position(|it| it.id.is_some() && it.id == selected_id) {
+            self.selected = index;
+        }
+        self.clamp_selection(visible.len());
+    }
+
+    pub fn record_speeds(&mut self, download: i64, upload: i64) {
+        for (history, speed) in [(&mut self.download_history, download), (&mut self.upload_history, upload)] {
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+            history.push_back(speed.max(0) as u64);
+        }
+    }
+
+    fn clamp_selection(&mut self, len: usize) {
+        self.selected = self.selected.min(len.saturating_sub(1));
+    }
+
+    fn selected_id(&self) -> Option<Id> {
+        let torrent = self.selected_torrent()?;
+        match (torrent.hash_string, torrent.id) {
+            (Some(hash), _) => Some(Id::Hash(hash)),
+            (None, Some(id)) => Some(Id::Id(id)),
+            _ => None,
+        }
+    }
+
+    /// Updates the state for a key press, returning the call to make if the key asks for one
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Command> {
+        match self.mode.clone() {
+            Mode::Filter => {
+                match key.code {
+                    KeyCode::Enter => self.mode = Mode::Normal,
+                    KeyCode::Esc => {
+                        self.name_filter.clear();
+                        self.mode = Mode::Normal;
+                    }
+                    KeyCode::Backspace => {
+                        self.name_filter.pop();
+                    }
+                    KeyCode::Char(c) => self.name_filter.push(c),
+                    _ => {}
+                }
+                self.selected = 0;
+                None
+            }
+            Mode::ConfirmRemove(delete_local_data) => {
+                self.mode = Mode::Normal;
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => self.selected_id().map(|id| Command::Remove {
+                        id,
+                        delete_local_data,
+                    }),
+                    _ => {
+                        self.message = Some("Remove cancelled".to_string());
+                        None
+                    }
+                }
+            }
+            Mode::Normal => self.handle_normal_key(key),
+        }
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) -> Option<Command> {
+        let visible = self.visible().len();
+        let action = |action: TorrentAction, app: &App| app.selected_id().map(|id| Command::Action(action, id));
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc => {
+                self.name_filter.clear();
+                self.message = None;
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.selected += 1,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::PageDown => self.selected += 10,
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = visible.saturating_sub(1),
+            KeyCode::Char('o') => self.sort = (self.sort + 1) % SORT_KEYS.len(),
+            KeyCode::Char('O') => self.descending = !self.descending,
+            KeyCode::Char('f') => {
+                let index = View::ALL.iter().position(|it| *it == self.view).unwrap_or(0);
+                self.view = View::ALL[(index + 1) % View::ALL.len()];
+                self.selected = 0;
+            }
+            KeyCode::Char('/') => self.mode = Mode::Filter,
+            KeyCode::Tab => {
+                let index = Tab::ALL.iter().position(|it| *it == self.tab).unwrap_or(0);
+                self.tab = Tab::ALL[(index + 1) % Tab::ALL.len()];
+            }
+            KeyCode::Char('s') => return action(TorrentAction::Start, self),
+            KeyCode::Char('S') => return action(TorrentAction::StartNow, self),
+            KeyCode::Char('p') => return action(TorrentAction::Stop, self),
+            KeyCode::Char('v') => return action(TorrentAction::Verify, self),
+            KeyCode::Char('r') => return action(TorrentAction::Reannounce, self),
+            KeyCode::Char('d') if visible > 0 => self.mode = Mode::ConfirmRemove(false),
+            KeyCode::Char('D') if visible > 0 => self.mode = Mode::ConfirmRemove(true),
+            _ => {}
+        }
+        self.clamp_selection(self.visible().len());
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.set_torrents(
+            serde_json::from_str(
+                r#"[
+                    {"id": 1, "hashString": "a", "name": "Ubuntu", "status": 6, "queuePosition": 2, "rateDownload": 0},
+                    {"id": 2, "hashString": "b", "name": "Debian", "status": 4, "queuePosition": 0, "rateDownload": 50},
+                    {"id": 3, "hashString": "c", "name": "Fedora", "status": 0, "queuePosition": 1, "rateDownload": 0}
+                ]"#,
+            )
+            .unwrap(),
+        );
+        app
+    }
+
+    fn names(app: &App) -> Vec<String> {
+        app.visible().into_iter().filter_map(|it| it.name).collect()
+    }
+
+    #[test]
+    pub fn test_sort_and_filter() {
+        let mut app = app();
+        assert_eq!(names(&app), vec!["Debian", "Fedora", "Ubuntu"]);
+        app.handle_key(key('o'));
+        assert_eq!(app.sort_key(), SortKey::Name);
+        app.handle_key(key('O'));
+        assert_eq!(names(&app), vec!["Ubuntu", "Fedora", "Debian"]);
+        app.handle_key(key('f'));
+        assert_eq!(names(&app), vec!["Debian"]);
+        app.handle_key(key('f'));
+        app.handle_key(key('/'));
+        for c in "UBU".chars() {
+            app.handle_key(key(c));
+        }
+        assert_eq!(app.mode, Mode::Filter);
+        assert_eq!(names(&app), vec!["Ubuntu"]);
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(names(&app), vec!["Ubuntu"]);
+    }
+
+    #[test]
+    pub fn test_commands() {
+        let mut app = app();
+        app.handle_key(key('j'));
+        assert_eq!(app.handle_key(key('p')), Some(Command::Action(TorrentAction::Stop, Id::Hash("c".to_string()))));
+        // the selection follows the torrent when the list changes
+        let mut torrents = app.torrents.clone();
+        torrents[1].queue_position = Some(5);
+        app.set_torrents(torrents);
+        assert_eq!(app.selected, 0);
+        assert_eq!(app.handle_key(key('D')), None);
+        assert_eq!(app.mode, Mode::ConfirmRemove(true));
+        assert_eq!(
+            app.handle_key(key('y')),
+            Some(Command::Remove {
+                id: Id::Hash("c".to_string()),
+                delete_local_data: true
+            })
+        );
+        app.handle_key(key('d'));
+        assert_eq!(app.handle_key(key('n')), None);
+        assert_eq!(app.message.as_deref(), Some("Remove cancelled"));
+    }
+
+    #[test]
+    pub fn test_speed_history() {
+        let mut app = App::new();
+        for i in 0..HISTORY + 5 {
+            app.record_speeds(i as i64, -1);
+        }
+        assert_eq!(app.download_history.len(), HISTORY);
+        assert_eq!(app.download_history.front(), Some(&5));
+        assert_eq!(app.upload_history.back(), Some(&0));
+    }
+}
@@ -0,0 +1,160 @@
+//! Terminal UI to monitor and control the torrents of a Transmission daemon.
+//!
+//! The daemon is found the same way as by the `transmission-rpc` command line tool.
+
+mod app;
+mod ui;
+
+use std::process;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+use transmission_rpc::config::ConnectionArgs;
+use transmission_rpc::types::{Id, Result, TorrentGetField};
+use transmission_rpc::{RetryPolicy, TransClient};
+
+use app::{App, Command};
+
+#[derive(Parser, Debug)]
+#[command(name = "transmission-tui", version, about = "Terminal UI for a Transmission daemon")]
+struct Cli {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+    /// seconds between two refreshes
+    #[arg(short, long, default_value = "2")]
+    interval: u64,
+}
+
+const LIST_FIELDS: [TorrentGetField; 15] = [
+    TorrentGetField::Id,
+    TorrentGetField::HashString,
+    TorrentGetField::Name,
+    TorrentGetField::Status,
+    TorrentGetField::Error,
+    TorrentGetField::Errorstring,
+    TorrentGetField::Isstalled,
+    TorrentGetField::Percentdone,
+    TorrentGetField::Totalsize,
+    TorrentGetField::Ratedownload,
+    TorrentGetField::Rateupload,
+    TorrentGetField::Uploadratio,
+    TorrentGetField::Eta,
+    TorrentGetField::Queueposition,
+    TorrentGetField::Downloaddir,
+];
+
+const DETAIL_FIELDS: [TorrentGetField; 5] = [
+    TorrentGetField::Id,
+    TorrentGetField::Peers,
+    TorrentGetField::Trackerstats,
+    TorrentGetField::Files,
+    TorrentGetField::Filestats,
+];
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    let client = match connect(&cli) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, client, Duration::from_secs(cli.interval.max(1))).await;
+    ratatui::restore();
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn connect(cli: &Cli) -> Result<TransClient> {
+    let mut client = cli.connection.client()?;
+    // the next refresh is the retry, backing off would only freeze the screen
+    client.set_retry_policy(RetryPolicy::none());
+    Ok(client)
+}
+
+async fn run(terminal: &mut DefaultTerminal, mut client: TransClient, interval: Duration) -> Result<()> {
+    let mut app = App::new();
+    let mut last_refresh: Option<Instant> = None;
+    while !app.quit {
+        let due = match last_refresh {
+            Some(it) => it.elapsed() >= interval,
+            None => true,
+        };
+        if due {
+            if let Err(e) = refresh(&mut client, &mut app).await {
+                app.message = Some(format!("Refresh failed: {}", e));
+            }
+            last_refresh = Some(Instant::now());
+        }
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+
+        let timeout = interval.saturating_sub(last_refresh.map(|it| it.elapsed()).unwrap_or_default());
+        if !event::poll(timeout.min(Duration::from_millis(250)))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let previous = app.selected_torrent().and_then(|it| it.id);
+            if let Some(command) = app.handle_key(key) {
+                app.message = Some(match execute(&mut client, command).await {
+                    Ok(done) => done,
+                    Err(e) => format!("Failed: {}", e),
+                });
+                last_refresh = None;
+            } else if app.selected_torrent().and_then(|it| it.id) != previous {
+                // load the detail of the newly selected torrent right away
+                last_refresh = None;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn refresh(client: &mut TransClient, app: &mut App) -> Result<()> {
+    let response = client.torrent_get(Some(LIST_FIELDS.to_vec()), None).await?;
+    if !response.is_ok() {
+        return Err(response.result.into());
+    }
+    app.set_torrents(response.arguments.torrents);
+
+    app.detail = match app.selected_torrent().and_then(|it| it.id) {
+        Some(id) => {
+            let response = client.torrent_get(Some(DETAIL_FIELDS.to_vec()), Some(vec![Id::Id(id)])).await?;
+            response.arguments.torrents.into_iter().next()
+        }
+        None => None,
+    };
+
+    let stats = client.session_stats().await?.arguments;
+    app.record_speeds(stats.download_speed, stats.upload_speed);
+    Ok(())
+}
+
+/// Makes the call asked for, returning the message to show
+async fn execute(client: &mut TransClient, command: Command) -> Result<String> {
+    let (response, done) = match command {
+        Command::Action(action, id) => {
+            let done = format!("{} sent", action.to_str());
+            (client.torrent_action(action, vec![id]).await?, done)
+        }
+        Command::Remove { id, delete_local_data } => (
+            client.torrent_remove(vec![id], delete_local_data).await?,
+            "Torrent removed".to_string(),
+        ),
+    };
+    if response.is_ok() {
+        Ok(done)
+    } else {
+        Err(response.result.into())
+    }
+}
@@ -0,0 +1,335 @@
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Sparkline, Table, TableState, Tabs};
+use ratatui::Frame;
+use transmission_rpc::filter::SortKey;
+use transmission_rpc::types::{Priority, Torrent, TorrentStatus};
+
+use crate::app::{App, Mode, Tab};
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Percentage(55),
+            Constraint::Min(8),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+    draw_header(frame, app, rows[0]);
+    draw_torrents(frame, app, rows[1]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .split(rows[2]);
+    draw_detail(frame, app, bottom[0]);
+    draw_speed(frame, app, bottom[1]);
+    draw_status(frame, app, rows[3]);
+}
+
+fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
+    let down = app.download_history.back().copied().unwrap_or(0) as i64;
+    let up = app.upload_history.back().copied().unwrap_or(0) as i64;
+    let mut spans = vec![
+        Span::styled("transmission-tui", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!("  view: {}", app.view.name())),
+        Span::raw(format!(
+            "  sort: {} {}",
+            sort_name(app.sort_key()),
+            if app.descending { "desc" } else { "asc" }
+        )),
+    ];
+    if !app.name_filter.is_empty() {
+        spans.push(Span::raw(format!("  filter: {}", app.name_filter)));
+    }
+    spans.push(Span::raw(format!("  down {}  up {}", speed(down), speed(up))));
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+fn draw_torrents(frame: &mut Frame, app: &App, area: Rect) {
+    let header = Row::new(["ID", "Name", "Status", "Done", "Size", "Down", "Up", "Ratio", "ETA"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows: Vec<Row> = app
+        .visible()
+        .iter()
+        .map(|torrent| {
+            let row = Row::new(vec![
+                Cell::from(torrent.id.map(|it| it.to_string()).unwrap_or_default()),
+                Cell::from(torrent.name.clone().unwrap_or_default()),
+                Cell::from(status(torrent)),
+                Cell::from(percent(torrent.percent_done)),
+                Cell::from(torrent.total_size.map(size).unwrap_or_default()),
+                Cell::from(torrent.rate_download.map(speed).unwrap_or_default()),
+                Cell::from(torrent.rate_upload.map(speed).unwrap_or_default()),
+                Cell::from(torrent.upload_ratio.map(|it| format!("{:.2}", it)).unwrap_or_default()),
+                Cell::from(torrent.eta.map(eta).unwrap_or_default()),
+            ]);
+            match torrent.error {
+                Some(error) if error != 0 => row.style(Style::default().fg(Color::Red)),
+                _ => row,
+            }
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(5),
+        Constraint::Min(20),
+        Constraint::Length(12),
+        Constraint::Length(7),
+        Constraint::Length(10),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(6),
+        Constraint::Length(8),
+    ];
+    let title = format!(" Torrents ({}/{}) ", rows.len(), app.torrents.len());
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_detail(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(
+        app.selected_torrent()
+            .and_then(|it| it.name)
+            .map(|it| format!(" {} ", it))
+            .unwrap_or_default(),
+    );
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+    let selected = Tab::ALL.iter().position(|it| *it == app.tab).unwrap_or(0);
+    let tabs = Tabs::new(Tab::ALL.iter().map(|it| it.name()))
+        .select(selected)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_widget(tabs, parts[0]);
+
+    let detail = match &app.detail {
+        Some(detail) if app.selected_torrent().is_some_and(|it| it.id == detail.id) => detail,
+        _ => return,
+    };
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let table = match app.tab {
+        Tab::Peers => Table::new(
+            detail.peers.iter().flatten().map(|peer| {
+                Row::new(vec![
+                    peer.address.clone().unwrap_or_default(),
+                    peer.client_name.clone().unwrap_or_default(),
+                    peer.flag_str.clone().unwrap_or_default(),
+                    percent(peer.progress),
+                    peer.rate_to_client.map(speed).unwrap_or_default(),
+                    peer.rate_to_peer.map(speed).unwrap_or_default(),
+                ])
+            }),
+            [
+                Constraint::Length(40),
+                Constraint::Min(15),
+                Constraint::Length(8),
+                Constraint::Length(7),
+                Constraint::Length(11),
+                Constraint::Length(11),
+            ],
+        )
+        .header(Row::new(["Address", "Client", "Flags", "Done", "Down", "Up"]).style(bold)),
+        Tab::Trackers => Table::new(
+            detail.tracker_stats.iter().flatten().map(|tracker| {
+                Row::new(vec![
+                    tracker.tier.map(|it| it.to_string()).unwrap_or_default(),
+                    tracker.host.clone().unwrap_or_default(),
+                    tracker.last_announce_result.clone().unwrap_or_default(),
+                    tracker.seeder_count.map(|it| it.to_string()).unwrap_or_default(),
+                    tracker.leecher_count.map(|it| it.to_string()).unwrap_or_default(),
+                ])
+            }),
+            [
+                Constraint::Length(4),
+                Constraint::Min(20),
+                Constraint::Min(15),
+                Constraint::Length(7),
+                Constraint::Length(8),
+            ],
+        )
+        .header(Row::new(["Tier", "Host", "Last announce", "Seeds", "Leechers"]).style(bold)),
+        Tab::Files => Table::new(
+            detail.torrent_files().into_iter().flatten().map(|file| {
+                Row::new(vec![
+                    file.path.join("/"),
+                    size(file.length),
+                    percent(Some(file.percent_done())),
+                    match (file.wanted, file.priority) {
+                        (false, _) => "skip",
+                        (true, Priority::High) => "high",
+                        (true, Priority::Normal) => "normal",
+                        (true, Priority::Low) => "low",
+                    }
+                    .to_string(),
+                ])
+            }),
+            [
+                Constraint::Min(30),
+                Constraint::Length(10),
+                Constraint::Length(7),
+                Constraint::Length(8),
+            ],
+        )
+        .header(Row::new(["Path", "Size", "Done", "Priority"]).style(bold)),
+    };
+    frame.render_widget(table, parts[1]);
+}
+
+fn draw_speed(frame: &mut Frame, app: &App, area: Rect) {
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    let width = parts[0].width.saturating_sub(2) as usize;
+    for (history, name, color, area) in [
+        (&app.download_history, "Down", Color::Green, parts[0]),
+        (&app.upload_history, "Up", Color::Blue, parts[1]),
+    ] {
+        let data: Vec<u64> = history.iter().skip(history.len().saturating_sub(width)).copied().collect();
+        let max = data.iter().copied().max().unwrap_or(0);
+        let sparkline = Sparkline::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} (max {}) ", name, speed(max as i64))),
+            )
+            .data(&data)
+            .style(Style::default().fg(color));
+        frame.render_widget(sparkline, area);
+    }
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let text = match &app.mode {
+        Mode::Filter => format!("/{}", app.name_filter),
+        Mode::ConfirmRemove(delete) => format!(
+            "Remove {}{}? (y/n)",
+            app.selected_torrent().and_then(|it| it.name).unwrap_or_default(),
+            if *delete { " and its data" } else { "" }
+        ),
+        Mode::Normal => match &app.message {
+            Some(message) => message.clone(),
+            None => "q quit  s start  S start now  p stop  v verify  r reannounce  d remove  D remove+data  \
+                     o sort  O reverse  f view  / filter  Tab detail"
+                .to_string(),
+        },
+    };
+    frame.render_widget(Paragraph::new(text).style(Style::default().fg(Color::Yellow)), area);
+}
+
+fn sort_name(key: SortKey) -> &'static str {
+    match key {
+        SortKey::Id => "id",
+        SortKey::Name => "name",
+        SortKey::Ratio => "ratio",
+        SortKey::TotalSize => "size",
+        SortKey::PercentDone => "done",
+        SortKey::AddedDate => "added",
+        SortKey::DoneDate => "finished",
+        SortKey::ActivityDate => "activity",
+        SortKey::RateDownload => "download speed",
+        SortKey::RateUpload => "upload speed",
+        SortKey::QueuePosition => "queue",
+    }
+}
+
+fn status(torrent: &Torrent) -> String {
+    if torrent.error.is_some_and(|it| it != 0) {
+        return "error".to_string();
+    }
+    match torrent.torrent_status() {
+        Some(TorrentStatus::Downloading) if torrent.is_stalled == Some(true) => "stalled".to_string(),
+        Some(status) => status.to_str().to_string(),
+        None => String::new(),
+    }
+}
+
+fn percent(done: Option<f32>) -> String {
+    done.map(|it| format!("{:.1}%", it * 100.0)).unwrap_or_default()
+}
+
+/// Size in bytes with a binary unit, e.g. `1.5 GiB`
+pub fn size(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+pub fn speed(bytes_per_second: i64) -> String {
+    format!("{}/s", size(bytes_per_second))
+}
+
+/// Remaining time in seconds as `1h02m`, empty when unknown (-1) or not applicable (-2)
+pub fn eta(seconds: i64) -> String {
+    match seconds {
+        s if s < 0 => String::new(),
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{:02}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+        s => format!("{}d{:02}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_units() {
+        assert_eq!(size(512), "512 B");
+        assert_eq!(size(1536), "1.5 KiB");
+        assert_eq!(size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+        assert_eq!(speed(2048), "2.0 KiB/s");
+        assert_eq!(eta(-1), "");
+        assert_eq!(eta(59), "59s");
+        assert_eq!(eta(3725), "1h02m");
+        assert_eq!(eta(90000), "1d01h");
+    }
+
+    #[test]
+    pub fn test_draw() {
+        use ratatui::backend::TestBackend;
+        use ratatui::Terminal;
+
+        let mut app = App::new();
+        app.set_torrents(
+            serde_json::from_str(r#"[{"id": 7, "name": "Ubuntu", "status": 4, "percentDone": 0.5, "rateDownload": 2048}]"#)
+                .unwrap(),
+        );
+        app.detail = serde_json::from_str(
+            r#"{"id": 7, "peers": [{"address": "10.0.0.2", "clientName": "qBittorrent", "progress": 1.0}]}"#,
+        )
+        .unwrap();
+        app.record_speeds(2048, 10);
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(120)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>() + "\n")
+            .collect();
+        for text in ["Torrents (1/1)", "Ubuntu", "downloading", "50.0%", "10.0.0.2", "qBittorrent", "Down (max 2.0 KiB/s)"] {
+            assert!(screen.contains(text), "{} not in\n{}", text, screen);
+        }
+    }
+}
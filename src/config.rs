@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use serde::Deserialize;
+
+use crate::types::{BasicAuth, Result};
+use crate::TransClient;
+
+/// Options choosing the daemon, shared by the binaries.
+///
+/// The daemon is taken from `--url`/`--user`/`--password`, then from the profile
+/// (`--profile` or the config's default one), then from `TURL`/`TUSER`/`TPWD`.
+/// User and password are only taken from where the url is, or from a place with the same url.
+#[derive(Args, Debug, Clone, Default)]
+#[command(about = None, long_about = None)]
+pub struct ConnectionArgs {
+    /// config file, defaults to transmission-rpc/config.toml in the user's config dir
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// profile of the config file to use
+    #[arg(short, long, global = true)]
+    pub profile: Option<String>,
+    /// RPC url, e.g. http://localhost:9091/transmission/rpc or unix:/path/to/socket
+    #[arg(long, global = true)]
+    pub url: Option<String>,
+    #[arg(long, global = true)]
+    pub user: Option<String>,
+    #[arg(long, global = true)]
+    pub password: Option<String>,
+}
+
+impl ConnectionArgs {
+    /// The daemon to use, from the flags, the config file and the environment
+    pub fn resolve(&self) -> Result<Profile> {
+        let config = Config::load(self.config.as_deref())?;
+        let flags = Profile {
+            url: self.url.clone(),
+            user: self.user.clone(),
+            password: self.password.clone(),
+        };
+        Ok(flags.or(config.profile(self.profile.as_deref())?).or(Profile::from_env()))
+    }
+
+    /// Returns a client for the daemon to use
+    pub fn client(&self) -> Result<TransClient> {
+        self.resolve()?.client()
+    }
+}
+
+/// Contents of the config file, e.g.
+///
+/// ```toml
+/// default = "home"
+///
+/// [profiles.home]
+/// url = "http://nas:9091/transmission/rpc"
+/// user = "transmission"
+/// password = "secret"
+///
+/// [profiles.local]
+/// url = "unix:/run/transmission/rpc.sock"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    /// profile used when none is given on the command line
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// How to reach one daemon
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    pub url: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/transmission-rpc/config.toml` or its platform equivalent
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|it| it.join("transmission-rpc").join("config.toml"))
+    }
+
+    /// Reads `path`, or the default path if None.
+    /// A missing file at the default path is an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Config::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        if !required && !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        Config::parse(&content).map_err(|e| format!("Invalid config {}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(content: &str) -> Result<Config> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Returns the profile `name`, or the default profile if None.
+    /// Having no default profile is not an error, the daemon is then given by flags or environment.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        match name.or(self.default.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("Unknown profile {}", name).into()),
+            None => Ok(Profile::default()),
+        }
+    }
+}
+
+impl Profile {
+    /// Fills the fields left unset with those of `other`.
+    /// Credentials go with their url: those of `other` are only taken for its own url,
+    /// and a password only for its own user, so they are never sent to another daemon.
+    pub fn or(self, other: Profile) -> Profile {
+        if self.url.is_some() && self.url != other.url {
+            return self;
+        }
+        let password = match (&self.user, &other.user) {
+            (Some(user), Some(other_user)) if user != other_user => self.password,
+            (Some(_), None) => self.password,
+            _ => self.password.or(other.password),
+        };
+        Profile {
+            url: self.url.or(other.url),
+            user: self.user.or(other.user),
+            password,
+        }
+    }
+
+    /// Returns a client for the daemon of the profile, with basic auth if it has a user.
+    /// The url can be `unix:/path/to/socket` as well.
+    pub fn client(self) -> Result<TransClient> {
+        let url = self
+            .url
+            .ok_or("No daemon url: use --url, a profile or the TURL environment variable")?;
+        Ok(match self.user {
+            Some(user) => TransClient::with_auth(
+                &url,
+                BasicAuth {
+                    user,
+                    password: self.password.unwrap_or_default(),
+                },
+            ),
+            None => TransClient::new(&url),
+        })
+    }
+
+    /// The profile given by `TURL`, `TUSER` and `TPWD`, as the examples use
+    pub fn from_env() -> Profile {
+        Profile {
+            url: env::var("TURL").ok(),
+            user: env::var("TUSER").ok(),
+            password: env::var("TPWD").ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_profiles() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            default = "home"
+
+            [profiles.home]
+            url = "http://nas:9091/transmission/rpc"
+            user = "transmission"
+
+            [profiles.local]
+            url = "unix:/run/transmission/rpc.sock"
+            "#,
+        )?;
+        let home = config.profile(None)?;
+        assert_eq!(home.url.as_deref(), Some("http://nas:9091/transmission/rpc"));
+        let local = config.profile(Some("local"))?;
+        assert_eq!(local.user, None);
+        assert!(config.profile(Some("work")).is_err());
+        assert_eq!(Config::default().profile(None)?, Profile::default());
+
+        let merged = Profile {
+            url: None,
+            user: Some("admin".to_string()),
+            password: None,
+        }
+        .or(home);
+        assert_eq!(merged.user.as_deref(), Some("admin"));
+        assert_eq!(merged.url.as_deref(), Some("http://nas:9091/transmission/rpc"));
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_credentials_follow_the_url() {
+        let home = Profile {
+            url: Some("http://nas:9091/transmission/rpc".to_string()),
+            user: Some("transmission".to_string()),
+            password: Some("secret".to_string()),
+        };
+        let other_host = Profile {
+            url: Some("http://other:9091/transmission/rpc".to_string()),
+            user: None,
+            password: None,
+        };
+        assert_eq!(other_host.clone().or(home.clone()), other_host);
+
+        let same_host = Profile {
+            url: home.url.clone(),
+            user: None,
+            password: None,
+        };
+        assert_eq!(same_host.or(home.clone()), home);
+
+        let other_user = Profile {
+            url: None,
+            user: Some("admin".to_string()),
+            password: None,
+        }
+        .or(home.clone());
+        assert_eq!(other_user.url, home.url);
+        assert_eq!(other_user.password, None);
+    }
+}
@@ -0,0 +1,532 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::filter::TorrentFilter;
+use crate::types::{Result, SessionGet, SessionStats, Stats, Torrent, TorrentGetField, TorrentStatus};
+use crate::TransClient;
+
+/// What the exporter publishes.
+/// Per-torrent and per-tracker series grow with the number of torrents and trackers,
+/// they can be turned off or limited to keep the cardinality of the metrics in check.
+#[derive(Debug, Clone)]
+pub struct ExporterConfig {
+    /// publish ratio, progress, peers and speeds of each torrent
+    pub per_torrent: bool,
+    /// only torrents matching the filter get per-torrent series
+    pub torrent_filter: Option<TorrentFilter>,
+    /// at most this many torrents get per-torrent series, the most active first
+    pub max_torrents: Option<usize>,
+    /// add the torrent name as a label of per-torrent series, next to its hash
+    pub torrent_name_label: bool,
+    /// publish announce results grouped by tracker host
+    pub trackers: bool,
+    /// publish the free space of every download dir, rpc-version 15+
+    pub free_space: bool,
+}
+
+impl Default for ExporterConfig {
+    fn default() -> Self {
+        ExporterConfig {
+            per_torrent: true,
+            torrent_filter: None,
+            max_torrents: Some(100),
+            torrent_name_label: true,
+            trackers: true,
+            free_space: true,
+        }
+    }
+}
+
+/// Everything fetched from the daemon for one scrape
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub session: SessionGet,
+    pub stats: SessionStats,
+    pub torrents: Vec<Torrent>,
+    /// free bytes by download dir
+    pub free_space: BTreeMap<String, i64>,
+}
+
+impl ExporterConfig {
+    /// Fields to request with torrent-get
+    pub fn fields(&self) -> Vec<TorrentGetField> {
+        let mut fields = vec![
+            TorrentGetField::Id,
+            TorrentGetField::HashString,
+            TorrentGetField::Name,
+            TorrentGetField::Status,
+            TorrentGetField::Error,
+            TorrentGetField::Isstalled,
+            TorrentGetField::Downloaddir,
+        ];
+        if self.per_torrent {
+            fields.extend(vec![
+                TorrentGetField::Uploadratio,
+                TorrentGetField::Percentdone,
+                TorrentGetField::Peersconnected,
+                TorrentGetField::Ratedownload,
+                TorrentGetField::Rateupload,
+            ]);
+            for field in self.torrent_filter.iter().flat_map(|it| it.fields()) {
+                if !fields.contains(&field) {
+                    fields.push(field);
+                }
+            }
+        }
+        if self.trackers {
+            fields.push(TorrentGetField::Trackerstats);
+        }
+        fields
+    }
+
+    /// Fetches session, statistics, torrents and free space from the daemon
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    pub async fn snapshot(&self, client: &mut TransClient) -> Result<Snapshot> {
+        let session = client.session_get().await?.arguments;
+        let stats = client.session_stats().await?.arguments;
+        let response = client.torrent_get(Some(self.fields()), None).await?;
+        if !response.is_ok() {
+            return Err(From::from(format!("torrent-get failed: {}", response.result)));
+        }
+        let torrents = response.arguments.torrents;
+
+        let mut free_space = BTreeMap::new();
+        if self.free_space && session.rpc_version >= 15 {
+            let dirs: BTreeSet<String> = torrents
+                .iter()
+                .filter_map(|it| it.download_dir.clone())
+                .chain(std::iter::once(session.download_dir.clone()))
+                .collect();
+            for dir in dirs {
+                // a dir that vanished should not fail the whole scrape
+                match client.free_space(dir.clone()).await {
+                    Ok(response) if response.is_ok() => {
+                        free_space.insert(dir, response.arguments.size_bytes);
+                    }
+                    Ok(response) => debug!("free-space of {} failed: {}", dir, response.result),
+                    Err(e) => debug!("free-space of {} failed: {}", dir, e),
+                }
+            }
+        }
+        Ok(Snapshot {
+            session,
+            stats,
+            torrents,
+            free_space,
+        })
+    }
+
+    /// Fetches a snapshot and renders it.
+    /// When the daemon cannot be reached only `transmission_up 0` is published.
+    pub async fn scrape(&self, client: &mut TransClient) -> String {
+        match self.snapshot(client).await {
+            Ok(snapshot) => self.render(&snapshot),
+            Err(e) => {
+                warn!("Scrape failed: {}", e);
+                let mut out = String::new();
+                metric(&mut out, "transmission_up", "gauge", "Whether the daemon answered", &[(vec![], 0.0)]);
+                out
+            }
+        }
+    }
+
+    /// Renders a snapshot in the Prometheus text format
+    pub fn render(&self, snapshot: &Snapshot) -> String {
+        let mut out = String::new();
+        let session = &snapshot.session;
+        let stats = &snapshot.stats;
+        metric(&mut out, "transmission_up", "gauge", "Whether the daemon answered", &[(vec![], 1.0)]);
+        metric(
+            &mut out,
+            "transmission_info",
+            "gauge",
+            "Version of the daemon",
+            &[(
+                vec![
+                    ("version", session.version.clone()),
+                    ("rpc_version", session.rpc_version.to_string()),
+                ],
+                1.0,
+            )],
+        );
+        metric(
+            &mut out,
+            "transmission_download_speed_bytes",
+            "gauge",
+            "Current download speed in bytes per second",
+            &[(vec![], stats.download_speed as f64)],
+        );
+        metric(
+            &mut out,
+            "transmission_upload_speed_bytes",
+            "gauge",
+            "Current upload speed in bytes per second",
+            &[(vec![], stats.upload_speed as f64)],
+        );
+        metric(
+            &mut out,
+            "transmission_alt_speed_enabled",
+            "gauge",
+            "Whether the alternative speed limits are on",
+            &[(vec![], if session.alt_speed_enabled { 1.0 } else { 0.0 })],
+        );
+
+        let scopes = [("current", &stats.current_stats), ("cumulative", &stats.cumulative_stats)];
+        let by_scope = |value: &dyn Fn(&Stats) -> f64| -> Vec<(Vec<(&str, String)>, f64)> {
+            scopes
+                .iter()
+                .map(|(scope, it)| (vec![("scope", scope.to_string())], value(it)))
+                .collect()
+        };
+        metric(
+            &mut out,
+            "transmission_downloaded_bytes_total",
+            "counter",
+            "Bytes downloaded since the daemon started (current) or ever (cumulative)",
+            &by_scope(&|it| it.downloaded_bytes as f64),
+        );
+        metric(
+            &mut out,
+            "transmission_uploaded_bytes_total",
+            "counter",
+            "Bytes uploaded since the daemon started (current) or ever (cumulative)",
+            &by_scope(&|it| it.uploaded_bytes as f64),
+        );
+        metric(
+            &mut out,
+            "transmission_files_added_total",
+            "counter",
+            "Files added since the daemon started (current) or ever (cumulative)",
+            &by_scope(&|it| it.files_added as f64),
+        );
+        metric(
+            &mut out,
+            "transmission_active_seconds_total",
+            "counter",
+            "Seconds the daemon was running since it started (current) or ever (cumulative)",
+            &by_scope(&|it| it.seconds_active as f64),
+        );
+
+        let torrents = &snapshot.torrents;
+        let by_status: Vec<(Vec<(&str, String)>, f64)> = (0..=6)
+            .filter_map(TorrentStatus::from_i64)
+            .map(|status| {
+                let count = torrents.iter().filter(|it| it.torrent_status() == Some(status)).count();
+                (vec![("status", status.to_str().to_string())], count as f64)
+            })
+            .collect();
+        metric(&mut out, "transmission_torrents", "gauge", "Number of torrents by status", &by_status);
+        let errored = torrents.iter().filter(|it| it.error.is_some_and(|e| e != 0)).count();
+        metric(
+            &mut out,
+            "transmission_torrents_errored",
+            "gauge",
+            "Number of torrents with an error",
+            &[(vec![], errored as f64)],
+        );
+        let stalled = torrents.iter().filter(|it| it.is_stalled == Some(true)).count();
+        metric(
+            &mut out,
+            "transmission_torrents_stalled",
+            "gauge",
+            "Number of torrents without any peer to exchange with",
+            &[(vec![], stalled as f64)],
+        );
+
+        if self.per_torrent {
+            self.render_torrents(&mut out, torrents);
+        }
+        if self.trackers {
+            render_trackers(&mut out, torrents);
+        }
+        if !snapshot.free_space.is_empty() {
+            let free: Vec<(Vec<(&str, String)>, f64)> = snapshot
+                .free_space
+                .iter()
+                .map(|(dir, bytes)| (vec![("dir", dir.clone())], *bytes as f64))
+                .collect();
+            metric(
+                &mut out,
+                "transmission_free_space_bytes",
+                "gauge",
+                "Free space in a download dir",
+                &free,
+            );
+        }
+        out
+    }
+
+    fn render_torrents(&self, out: &mut String, torrents: &[Torrent]) {
+        let mut selected: Vec<&Torrent> = torrents
+            .iter()
+            .filter(|it| it.hash_string.is_some())
+            .filter(|it| match &self.torrent_filter {
+                Some(filter) => filter.matches(it),
+                None => true,
+            })
+            .collect();
+        selected.sort_by_key(|it| {
+            (
+                std::cmp::Reverse(it.rate_download.unwrap_or(0) + it.rate_upload.unwrap_or(0)),
+                it.id,
+            )
+        });
+        if let Some(max) = self.max_torrents {
+            selected.truncate(max);
+        }
+        let series = |value: &dyn Fn(&Torrent) -> Option<f64>| -> Vec<(Vec<(&str, String)>, f64)> {
+            selected
+                .iter()
+                .filter_map(|torrent| {
+                    let mut labels = vec![("hash", torrent.hash_string.clone().unwrap_or_default())];
+                    if self.torrent_name_label {
+                        labels.push(("name", torrent.name.clone().unwrap_or_default()));
+                    }
+                    Some((labels, value(torrent)?))
+                })
+                .collect()
+        };
+        metric(
+            out,
+            "transmission_torrent_ratio",
+            "gauge",
+            "Upload ratio of a torrent",
+            &series(&|it| it.upload_ratio.map(f64::from)),
+        );
+        metric(
+            out,
+            "transmission_torrent_progress",
+            "gauge",
+            "Part of the wanted data downloaded, from 0 to 1",
+            &series(&|it| it.percent_done.map(f64::from)),
+        );
+        metric(
+            out,
+            "transmission_torrent_peers_connected",
+            "gauge",
+            "Peers connected to a torrent",
+            &series(&|it| it.peers_connected.map(|p| p as f64)),
+        );
+        metric(
+            out,
+            "transmission_torrent_download_speed_bytes",
+            "gauge",
+            "Download speed of a torrent in bytes per second",
+            &series(&|it| it.rate_download.map(|r| r as f64)),
+        );
+        metric(
+            out,
+            "transmission_torrent_upload_speed_bytes",
+            "gauge",
+            "Upload speed of a torrent in bytes per second",
+            &series(&|it| it.rate_upload.map(|r| r as f64)),
+        );
+    }
+}
+
+/// Announce results of all torrents, grouped by tracker host
+fn render_trackers(out: &mut String, torrents: &[Torrent]) {
+    let mut hosts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for tracker in torrents.iter().flat_map(|it| it.tracker_stats.iter().flatten()) {
+        if tracker.has_announced != Some(true) {
+            continue;
+        }
+        let host = tracker.host.clone().unwrap_or_default();
+        let entry = hosts.entry(host).or_default();
+        if tracker.last_announce_succeeded == Some(true) {
+            entry.0 += 1;
+        } else {
+            entry.1 += 1;
+        }
+    }
+    let series = |value: &dyn Fn(&(usize, usize)) -> usize| -> Vec<(Vec<(&str, String)>, f64)> {
+        hosts
+            .iter()
+            .map(|(host, counts)| (vec![("host", host.clone())], value(counts) as f64))
+            .collect()
+    };
+    metric(
+        out,
+        "transmission_tracker_announce_succeeded",
+        "gauge",
+        "Torrents whose last announce to the tracker succeeded",
+        &series(&|it| it.0),
+    );
+    metric(
+        out,
+        "transmission_tracker_announce_failed",
+        "gauge",
+        "Torrents whose last announce to the tracker failed",
+        &series(&|it| it.1),
+    );
+}
+
+/// Writes one metric family, skipping it when it has no series
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, series: &[(Vec<(&str, String)>, f64)]) {
+    if series.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in series {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+            .collect();
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+        }
+    }
+}
+
+/// Escapes a label value as the text format requires
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Serves the metrics on `http://addr/metrics`, scraping the daemon on every request.
+/// Requests are handled one at a time, which is plenty for a Prometheus server.
+///
+/// # Errors
+///
+/// An error if `addr` cannot be bound, never returns otherwise
+pub async fn serve(addr: SocketAddr, mut client: TransClient, config: ExporterConfig) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving metrics on http://{}/metrics", listener.local_addr()?);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // e.g. out of file descriptors, which the next scrapes may not be
+                warn!("Unable to accept a connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        if let Err(e) = handle(stream, &mut client, &config).await {
+            debug!("Request from {} failed: {}", peer, e);
+        }
+    }
+}
+
+async fn handle(mut stream: TcpStream, client: &mut TransClient, config: &ExporterConfig) -> Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|it| it == b"\r\n\r\n") {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buffer)).await??;
+        if read == 0 || request.len() > 16 * 1024 {
+            return Err(From::from("Incomplete request"));
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", "text/plain; version=0.0.4", config.scrape(client).await),
+        (Some("GET"), Some("/")) => (
+            "200 OK",
+            "text/html",
+            "<html><body><a href=\"/metrics\">Metrics</a></body></html>".to_string(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            session: serde_json::from_str(
+                r#"{"alt-speed-down": 50, "alt-speed-enabled": false, "alt-speed-up": 50,
+                    "blocklist-enabled": false, "download-dir": "/downloads", "encryption": "preferred",
+                    "rpc-version-minimum": 1, "rpc-version": 17, "version": "4.0.5"}"#,
+            )
+            .unwrap(),
+            stats: serde_json::from_str(
+                r#"{"torrentCount": 2, "activeTorrentCount": 1, "pausedTorrentCount": 1,
+                    "downloadSpeed": 1000, "uploadSpeed": 20,
+                    "current-stats": {"filesAdded": 1, "downloadedBytes": 10, "uploadedBytes": 5, "secondsActive": 60},
+                    "cumulative-stats": {"filesAdded": 9, "downloadedBytes": 100, "uploadedBytes": 50, "secondsActive": 600}}"#,
+            )
+            .unwrap(),
+            torrents: serde_json::from_str(
+                r#"[
+                    {"id": 1, "hashString": "aa", "name": "Say \"hi\"", "status": 4, "error": 0,
+                     "uploadRatio": 0.5, "percentDone": 0.25, "peersConnected": 3, "rateDownload": 1000, "rateUpload": 20,
+                     "trackerStats": [{"host": "tracker.example", "hasAnnounced": true, "lastAnnounceSucceeded": true}]},
+                    {"id": 2, "hashString": "bb", "name": "Idle", "status": 0, "error": 2,
+                     "uploadRatio": 2.0, "percentDone": 1.0, "peersConnected": 0, "rateDownload": 0, "rateUpload": 0,
+                     "trackerStats": [{"host": "tracker.example", "hasAnnounced": true, "lastAnnounceSucceeded": false}]}
+                ]"#,
+            )
+            .unwrap(),
+            free_space: vec![("/downloads".to_string(), 4096)].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    pub fn test_render() {
+        let text = ExporterConfig::default().render(&snapshot());
+        for line in &[
+            "transmission_up 1",
+            "transmission_info{version=\"4.0.5\",rpc_version=\"17\"} 1",
+            "transmission_download_speed_bytes 1000",
+            "transmission_downloaded_bytes_total{scope=\"cumulative\"} 100",
+            "transmission_torrents{status=\"downloading\"} 1",
+            "transmission_torrents{status=\"seeding\"} 0",
+            "transmission_torrents_errored 1",
+            "transmission_torrent_ratio{hash=\"aa\",name=\"Say \\\"hi\\\"\"} 0.5",
+            "transmission_torrent_peers_connected{hash=\"bb\",name=\"Idle\"} 0",
+            "transmission_tracker_announce_succeeded{host=\"tracker.example\"} 1",
+            "transmission_tracker_announce_failed{host=\"tracker.example\"} 1",
+            "transmission_free_space_bytes{dir=\"/downloads\"} 4096",
+            "# TYPE transmission_uploaded_bytes_total counter",
+        ] {
+            assert!(text.lines().any(|it| it == *line), "{} not in\n{}", line, text);
+        }
+    }
+
+    #[test]
+    pub fn test_cardinality_controls() {
+        let config = ExporterConfig {
+            max_torrents: Some(1),
+            torrent_name_label: false,
+            trackers: false,
+            ..ExporterConfig::default()
+        };
+        let text = config.render(&snapshot());
+        // only the most active torrent, without its name
+        assert!(text.contains("transmission_torrent_ratio{hash=\"aa\"} 0.5"));
+        assert!(!text.contains("hash=\"bb\""));
+        assert!(!text.contains("transmission_tracker_"));
+
+        let config = ExporterConfig {
+            per_torrent: false,
+            ..ExporterConfig::default()
+        };
+        assert!(!config.render(&snapshot()).contains("transmission_torrent_"));
+        assert!(!config.fields().contains(&TorrentGetField::Uploadratio));
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::filter::unix_now;
+use crate::state;
+use crate::types::{Result, TorrentAddArgs};
+use crate::TransClient;
+
+/// Gets the content of a feed
+pub trait Fetch: Send + Sync {
+    fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<String>>;
+}
+
+/// Fetches `http://` and `https://` urls, reads anything else as a local file,
+/// with or without a `file://` prefix
+#[derive(Debug, Default)]
+pub struct HttpFetch {
+    http: reqwest::Client,
+}
+
+impl Fetch for HttpFetch {
+    fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            if url.starts_with("http://") || url.starts_with("https://") {
+                let response = self.http.get(url).send().await?.error_for_status()?;
+                Ok(response.text().await?)
+            } else {
+                Ok(fs::read_to_string(url.trim_start_matches("file://"))?)
+            }
+        })
+    }
+}
+
+/// An item of an RSS feed or an entry of an Atom feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedItem {
+    pub title: String,
+    /// guid or id, the link if the feed has none
+    pub id: String,
+    /// enclosure, magnet URI or link of the item, in that order
+    pub link: String,
+}
+
+/// Items of an RSS 2.0 or Atom document having a title and a link
+///
+/// # Errors
+///
+/// XML parsing error
+pub fn parse_feed(xml: &str) -> Result<Vec<FeedItem>> {
+    let document = roxmltree::Document::parse(xml)?;
+    let items = document
+        .descendants()
+        .filter(|it| matches!(it.tag_name().name(), "item" | "entry"))
+        .filter_map(|node| {
+            let child = |name: &str| {
+                node.children()
+                    .find(|it| it.tag_name().name() == name)
+                    .and_then(|it| it.text())
+                    .map(|it| it.trim().to_string())
+                    .filter(|it| !it.is_empty())
+            };
+            let link_with = |rel: Option<&str>| {
+                node.children()
+                    .filter(|it| matches!(it.tag_name().name(), "link" | "enclosure"))
+                    .filter(|it| rel.is_none() || it.attribute("rel") == rel || it.tag_name().name() == "enclosure")
+                    .find_map(|it| it.attribute("href").or_else(|| it.attribute("url")))
+                    .map(str::to_string)
+            };
+            let link = link_with(Some("enclosure"))
+                .or_else(|| child("magnetURI"))
+                .or_else(|| child("link"))
+                .or_else(|| link_with(None))?;
+            let title = child("title")?;
+            let id = child("guid").or_else(|| child("id")).unwrap_or_else(|| link.clone());
+            Some(FeedItem { title, id, link })
+        })
+        .collect();
+    Ok(items)
+}
+
+/// Which items of a feed are added, and how
+#[derive(Debug, Clone)]
+pub struct FeedRule {
+    pub name: String,
+    /// titles must match it
+    pub pattern: Regex,
+    /// words the titles must all contain, ignoring case
+    pub include: Vec<String>,
+    /// words the titles must not contain, ignoring case
+    pub exclude: Vec<String>,
+    pub download_dir: Option<String>,
+    pub paused: bool,
+    /// rpc-version 17+
+    pub labels: Vec<String>,
+    /// add each episode (`S01E02` or `1x02` in the title) once, whichever release comes first
+    pub episode_dedup: bool,
+}
+
+impl FeedRule {
+    /// Returns a rule adding the items matching `pattern`, with episode dedup
+    ///
+    /// # Errors
+    ///
+    /// Invalid regex
+    pub fn new(name: &str, pattern: &str) -> Result<FeedRule> {
+        Ok(FeedRule {
+            name: name.to_string(),
+            pattern: Regex::new(pattern)?,
+            include: vec![],
+            exclude: vec![],
+            download_dir: None,
+            paused: false,
+            labels: vec![],
+            episode_dedup: true,
+        })
+    }
+
+    pub fn matches(&self, title: &str) -> bool {
+        let lowercase = title.to_lowercase();
+        self.pattern.is_match(title)
+            && self.include.iter().all(|it| lowercase.contains(&it.to_lowercase()))
+            && !self.exclude.iter().any(|it| lowercase.contains(&it.to_lowercase()))
+    }
+}
+
+/// Show and episode of a release title, e.g. `the expanse s02e05` for
+/// `The.Expanse.S02E05.1080p.WEB.h264-GROUP`
+pub fn episode_key(title: &str) -> Option<String> {
+    static EPISODE: OnceLock<Regex> = OnceLock::new();
+    let re = EPISODE.get_or_init(|| {
+        Regex::new(r"(?i)^(.*?)[\s._\-\[(]+(?:s(\d{1,2})[\s._]?e(\d{1,3})|(\d{1,2})x(\d{2,3}))\b").unwrap()
+    });
+    let captures = re.captures(title)?;
+    let show: Vec<String> = captures[1]
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|it| !it.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let number = |a: usize, b: usize| {
+        captures
+            .get(a)
+            .or_else(|| captures.get(b))
+            .and_then(|it| it.as_str().parse::<u32>().ok())
+    };
+    Some(format!("{} s{:02}e{:02}", show.join(" "), number(2, 4)?, number(3, 5)?))
+}
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub url: String,
+    /// the first rule matching an item decides how it is added
+    pub rules: Vec<FeedRule>,
+}
+
+/// An item added, or refused by the daemon
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub feed: String,
+    pub rule: String,
+    pub item: FeedItem,
+    /// name of the added torrent, or the reason it was refused
+    pub outcome: std::result::Result<Option<String>, String>,
+}
+
+/// What the downloader remembers between polls and restarts
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FeedState {
+    /// ids of the items already handled, by feed url, with the last time they were in the feed
+    seen: BTreeMap<String, BTreeMap<String, i64>>,
+    /// episode keys already added
+    episodes: BTreeSet<String>,
+}
+
+/// Polls feeds and adds the items matching their rules.
+///
+/// ```
+/// use transmission_rpc::feed::{Feed, FeedDownloader, FeedRule};
+/// # use transmission_rpc::types::Result;
+///
+/// # fn main() -> Result<()> {
+/// let mut rule = FeedRule::new("expanse", r"(?i)^the\.expanse\.")?;
+/// rule.include = vec!["1080p".to_string()];
+/// rule.exclude = vec!["hdr".to_string()];
+/// rule.download_dir = Some("/data/tv/The Expanse".to_string());
+/// rule.labels = vec!["tv".to_string()];
+/// let feeds = vec![Feed {
+///     url: "https://tracker.example.com/rss?passkey=abc".to_string(),
+///     rules: vec![rule],
+/// }];
+/// let downloader = FeedDownloader::with_state_file(feeds, "feeds.json".into())?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct FeedDownloader {
+    feeds: Vec<Feed>,
+    fetch: Box<dyn Fetch>,
+    state_path: Option<PathBuf>,
+    state: FeedState,
+}
+
+impl FeedDownloader {
+    pub fn new(feeds: Vec<Feed>) -> FeedDownloader {
+        FeedDownloader {
+            feeds,
+            fetch: Box::new(HttpFetch::default()),
+            state_path: None,
+            state: FeedState::default(),
+        }
+    }
+
+    /// Returns a downloader keeping its state in `path`, loading it if the file exists
+    ///
+    /// # Errors
+    ///
+    /// IO Error or Deserialization error of an existing state file
+    pub fn with_state_file(feeds: Vec<Feed>, path: PathBuf) -> Result<FeedDownloader> {
+        let state = state::load(&path)?;
+        Ok(FeedDownloader {
+            state_path: Some(path),
+            state,
+            ..FeedDownloader::new(feeds)
+        })
+    }
+
+    /// Sets how feeds are fetched, [`HttpFetch`] by default
+    pub fn set_fetch(&mut self, fetch: Box<dyn Fetch>) {
+        self.fetch = fetch;
+    }
+
+    /// Fetches the feeds and adds their new matching items, then saves the state.
+    /// A feed which cannot be fetched or parsed is skipped until the next poll.
+    ///
+    /// # Errors
+    ///
+    /// Any error calling the daemon, or IO Error of the state file.
+    /// The items added before the daemon failed are saved as seen all the same.
+    pub async fn poll(&mut self, client: &mut TransClient) -> Result<Vec<Download>> {
+        let mut downloads = vec![];
+        let result = self.add_new_items(client, &mut downloads).await;
+        let saved = self.save();
+        result?;
+        saved?;
+        Ok(downloads)
+    }
+
+    async fn add_new_items(&mut self, client: &mut TransClient, downloads: &mut Vec<Download>) -> Result<()> {
+        let mut rpc_version = None;
+        for feed in &self.feeds {
+            let items = match self.fetch.fetch(&feed.url).await.and_then(|xml| parse_feed(&xml)) {
+                Ok(items) => items,
+                Err(e) => {
+                    warn!("Unable to read feed {}: {}", feed.url, e);
+                    continue;
+                }
+            };
+            let episodes = &mut self.state.episodes;
+            let seen = self.state.seen.entry(feed.url.clone()).or_default();
+            let now = unix_now();
+            refresh_seen(seen, &items, now);
+            for item in items {
+                if seen.contains_key(&item.id) {
+                    continue;
+                }
+                let rule = match feed.rules.iter().find(|it| it.matches(&item.title)) {
+                    Some(rule) => rule,
+                    None => continue,
+                };
+                let episode = if rule.episode_dedup { episode_key(&item.title) } else { None };
+                if episode.as_ref().is_some_and(|it| episodes.contains(it)) {
+                    seen.insert(item.id, now);
+                    continue;
+                }
+                let mut add = TorrentAddArgs {
+                    filename: Some(item.link.clone()),
+                    download_dir: rule.download_dir.clone(),
+                    paused: if rule.paused { Some(true) } else { None },
+                    ..TorrentAddArgs::default()
+                };
+                if !rule.labels.is_empty() {
+                    let version = match rpc_version {
+                        Some(version) => version,
+                        None => *rpc_version.insert(client.rpc_version().await?),
+                    };
+                    if version >= 17 {
+                        add.labels = Some(rule.labels.clone());
+                    } else {
+                        warn!("Labels of rule {} ignored, they need rpc-version 17", rule.name);
+                    }
+                }
+                let response = client.torrent_add(add).await?;
+                // a refused link is not retried, the next release of the episode may do better
+                seen.insert(item.id.clone(), now);
+                let outcome = if response.is_ok() {
+                    if let Some(episode) = episode {
+                        episodes.insert(episode);
+                    }
+                    Ok(response.arguments.torrent_added.and_then(|it| it.name))
+                } else {
+                    Err(response.result)
+                };
+                downloads.push(Download {
+                    feed: feed.url.clone(),
+                    rule: rule.name.clone(),
+                    item,
+                    outcome,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Polls the feeds every `interval` and logs what was added
+    ///
+    /// # Errors
+    ///
+    /// The error of the first poll that fails, e.g. on an unreachable daemon;
+    /// feeds that cannot be fetched do not stop it
+    pub async fn watch(&mut self, client: &mut TransClient, interval: Duration) -> Result<()> {
+        loop {
+            for download in self.poll(client).await? {
+                match &download.outcome {
+                    Ok(_) => info!("Added {} ({})", download.item.title, download.rule),
+                    Err(e) => warn!("Unable to add {}: {}", download.item.title, e),
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        state::save(self.state_path.as_deref(), &self.state)
+    }
+}
+
+/// Items seen are forgotten once they have not been in their feed for this long, in seconds.
+/// Items only drop off feeds, so a short or failed fetch must not make them new again.
+const SEEN_EXPIRY: i64 = 30 * 24 * 3600;
+
+/// Marks the items of the feed as still in it, and forgets the ones long gone
+fn refresh_seen(seen: &mut BTreeMap<String, i64>, items: &[FeedItem], now: i64) {
+    for item in items {
+        if let Some(time) = seen.get_mut(&item.id) {
+            *time = now;
+        }
+    }
+    seen.retain(|_, time| now - *time < SEEN_EXPIRY);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_feed() -> Result<()> {
+        let rss = r#"<?xml version="1.0"?>
+            <rss version="2.0" xmlns:torrent="http://xmlns.ezrss.it/0.1/">
+              <channel>
+                <title>tracker</title>
+                <item>
+                  <title>The.Expanse.S02E05.1080p.WEB.h264-GROUP</title>
+                  <link>https://tracker.example.com/details/1</link>
+                  <guid>tracker-1</guid>
+                  <enclosure url="https://tracker.example.com/download/1.torrent" type="application/x-bittorrent"/>
+                </item>
+                <item>
+                  <title>Some Album</title>
+                  <link>https://tracker.example.com/details/2</link>
+                  <torrent:magnetURI>magnet:?xt=urn:btih:abc</torrent:magnetURI>
+                </item>
+                <item><title>No link</title></item>
+              </channel>
+            </rss>"#;
+        assert_eq!(
+            parse_feed(rss)?,
+            vec![
+                FeedItem {
+                    title: "The.Expanse.S02E05.1080p.WEB.h264-GROUP".to_string(),
+                    id: "tracker-1".to_string(),
+                    link: "https://tracker.example.com/download/1.torrent".to_string(),
+                },
+                FeedItem {
+                    title: "Some Album".to_string(),
+                    id: "magnet:?xt=urn:btih:abc".to_string(),
+                    link: "magnet:?xt=urn:btih:abc".to_string(),
+                },
+            ]
+        );
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+              <entry>
+                <title>Show 1x02 720p</title>
+                <id>urn:uuid:2</id>
+                <link rel="alternate" href="https://example.com/2"/>
+                <link rel="enclosure" href="https://example.com/2.torrent"/>
+              </entry>
+            </feed>"#;
+        let items = parse_feed(atom)?;
+        assert_eq!(items[0].id, "urn:uuid:2");
+        assert_eq!(items[0].link, "https://example.com/2.torrent");
+        assert!(parse_feed("<rss>").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_refresh_seen() {
+        let item = |id: &str| FeedItem {
+            title: id.to_string(),
+            id: id.to_string(),
+            link: id.to_string(),
+        };
+        let mut seen: BTreeMap<String, i64> = vec![("a".to_string(), 0), ("b".to_string(), 0)].into_iter().collect();
+        // an empty or truncated feed forgets nothing
+        refresh_seen(&mut seen, &[], 100);
+        refresh_seen(&mut seen, &[item("a")], 200);
+        assert_eq!(seen.len(), 2);
+        refresh_seen(&mut seen, &[], SEEN_EXPIRY + 100);
+        assert_eq!(seen.keys().collect::<Vec<_>>(), vec!["a"]);
+    }
+
+    #[test]
+    pub fn test_rules() -> Result<()> {
+        assert_eq!(
+            episode_key("The.Expanse.S02E05.1080p.WEB.h264-GROUP").as_deref(),
+            Some("the expanse s02e05")
+        );
+        assert_eq!(episode_key("the expanse - 2x05 [720p]").as_deref(), Some("the expanse s02e05"));
+        assert_eq!(episode_key("Some Album (2019) FLAC"), None);
+
+        let mut rule = FeedRule::new("expanse", r"(?i)^the[ .]expanse")?;
+        rule.include = vec!["1080P".to_string()];
+        rule.exclude = vec!["hdr".to_string()];
+        assert!(rule.matches("The.Expanse.S02E05.1080p.WEB.h264-GROUP"));
+        assert!(!rule.matches("The.Expanse.S02E05.1080p.HDR.WEB.h264-GROUP"));
+        assert!(!rule.matches("The.Expanse.S02E05.720p.WEB.h264-GROUP"));
+        assert!(!rule.matches("Expanse.S02E05.1080p"));
+        assert!(FeedRule::new("invalid", "(").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,387 @@
+use std::cmp::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::Url;
+
+use crate::types::{Torrent, TorrentGetField, TorrentStatus, Torrents};
+
+/// A predicate on torrents, composable with `and`, `or` and `not`.
+///
+/// A torrent lacking the field a predicate looks at does not match it,
+/// so request the fields given by `fields` with torrent-get.
+///
+/// ```
+/// use std::time::Duration;
+/// use transmission_rpc::filter::TorrentFilter;
+/// use transmission_rpc::types::TorrentStatus;
+///
+/// // seeding with ratio >= 2 and added more than 30 days ago
+/// let old_seeds = TorrentFilter::Status(TorrentStatus::Seeding)
+///     .and(TorrentFilter::RatioAtLeast(2.0))
+///     .and(TorrentFilter::AddedOlderThan(Duration::from_secs(30 * 24 * 3600)));
+/// // errored torrents in /data/tv
+/// let broken_tv = TorrentFilter::HasError.and(TorrentFilter::DownloadDir("/data/tv".to_string()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum TorrentFilter {
+    Status(TorrentStatus),
+    /// upload ratio is this or more
+    RatioAtLeast(f32),
+    RatioBelow(f32),
+    /// added to the daemon longer ago than the duration
+    AddedOlderThan(Duration),
+    /// finished downloading longer ago than the duration
+    DoneOlderThan(Duration),
+    /// no upload nor download for longer than the duration
+    InactiveFor(Duration),
+    /// seeded for the duration or longer in total
+    SeededAtLeast(Duration),
+    Label(String),
+    /// download dir is this path or below it
+    DownloadDir(String),
+    /// one of the trackers has this host name
+    TrackerHost(String),
+    HasError,
+    SizeAbove(i64),
+    SizeBelow(i64),
+    IsPrivate,
+    IsFinished,
+    IsStalled,
+    /// name contains the text, ignoring case
+    NameContains(String),
+    All(Vec<TorrentFilter>),
+    Any(Vec<TorrentFilter>),
+    Not(Box<TorrentFilter>),
+}
+
+impl TorrentFilter {
+    pub fn and(self, other: TorrentFilter) -> TorrentFilter {
+        match self {
+            TorrentFilter::All(mut filters) => {
+                filters.push(other);
+                TorrentFilter::All(filters)
+            }
+            it => TorrentFilter::All(vec![it, other]),
+        }
+    }
+
+    pub fn or(self, other: TorrentFilter) -> TorrentFilter {
+        match self {
+            TorrentFilter::Any(mut filters) => {
+                filters.push(other);
+                TorrentFilter::Any(filters)
+            }
+            it => TorrentFilter::Any(vec![it, other]),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> TorrentFilter {
+        TorrentFilter::Not(Box::new(self))
+    }
+
+    pub fn matches(&self, torrent: &Torrent) -> bool {
+        self.matches_at(torrent, unix_now())
+    }
+
+    /// Same as `matches`, with durations counted back from the unix timestamp `now`
+    pub fn matches_at(&self, torrent: &Torrent, now: i64) -> bool {
+        let older_than = |date: Option<i64>, duration: &Duration| {
+            date.is_some_and(|it| it > 0 && now - it > duration.as_secs() as i64)
+        };
+        match self {
+            TorrentFilter::Status(status) => torrent.torrent_status() == Some(*status),
+            TorrentFilter::RatioAtLeast(ratio) => torrent.upload_ratio.is_some_and(|it| it >= *ratio),
+            TorrentFilter::RatioBelow(ratio) => torrent.upload_ratio.is_some_and(|it| it < *ratio),
+            TorrentFilter::AddedOlderThan(duration) => older_than(torrent.added_date, duration),
+            TorrentFilter::DoneOlderThan(duration) => older_than(torrent.done_date, duration),
+            TorrentFilter::InactiveFor(duration) => older_than(torrent.activity_date, duration),
+            TorrentFilter::SeededAtLeast(duration) => torrent
+                .seconds_seeding
+                .is_some_and(|it| it >= duration.as_secs() as i64),
+            TorrentFilter::Label(label) => torrent
+                .labels
+                .as_ref()
+                .is_some_and(|it| it.contains(label)),
+            TorrentFilter::DownloadDir(dir) => torrent
+                .download_dir
+                .as_ref()
+                .is_some_and(|it| is_below(it, dir)),
+            TorrentFilter::TrackerHost(host) => torrent.trackers.iter().flatten().any(|it| {
+                Url::parse(&it.announce)
+                    .ok()
+                    .and_then(|url| url.host_str().map(|it| it.eq_ignore_ascii_case(host)))
+                    .unwrap_or(false)
+            }),
+            TorrentFilter::HasError => torrent.error.is_some_and(|it| it != 0),
+            TorrentFilter::SizeAbove(size) => torrent.total_size.is_some_and(|it| it > *size),
+            TorrentFilter::SizeBelow(size) => torrent.total_size.is_some_and(|it| it < *size),
+            TorrentFilter::IsPrivate => torrent.is_private == Some(true),
+            TorrentFilter::IsFinished => torrent.is_finished == Some(true),
+            TorrentFilter::IsStalled => torrent.is_stalled == Some(true),
+            TorrentFilter::NameContains(text) => torrent
+                .name
+                .as_ref()
+                .is_some_and(|it| it.to_lowercase().contains(&text.to_lowercase())),
+            TorrentFilter::All(filters) => filters.iter().all(|it| it.matches_at(torrent, now)),
+            TorrentFilter::Any(filters) => filters.iter().any(|it| it.matches_at(torrent, now)),
+            TorrentFilter::Not(filter) => !filter.matches_at(torrent, now),
+        }
+    }
+
+    /// Fields the filter looks at
+    pub fn fields(&self) -> Vec<TorrentGetField> {
+        let mut fields = vec![];
+        self.collect_fields(&mut fields);
+        fields
+    }
+
+    fn collect_fields(&self, fields: &mut Vec<TorrentGetField>) {
+        let needed = match self {
+            TorrentFilter::Status(_) => vec![TorrentGetField::Status],
+            TorrentFilter::RatioAtLeast(_) | TorrentFilter::RatioBelow(_) => vec![TorrentGetField::Uploadratio],
+            TorrentFilter::AddedOlderThan(_) => vec![TorrentGetField::Addeddate],
+            TorrentFilter::DoneOlderThan(_) => vec![TorrentGetField::Donedate],
+            TorrentFilter::InactiveFor(_) => vec![TorrentGetField::Activitydate],
+            TorrentFilter::SeededAtLeast(_) => vec![TorrentGetField::Secondsseeding],
+            TorrentFilter::Label(_) => vec![TorrentGetField::Labels],
+            TorrentFilter::DownloadDir(_) => vec![TorrentGetField::Downloaddir],
+            TorrentFilter::TrackerHost(_) => vec![TorrentGetField::Trackers],
+            TorrentFilter::HasError => vec![TorrentGetField::Error, TorrentGetField::Errorstring],
+            TorrentFilter::SizeAbove(_) | TorrentFilter::SizeBelow(_) => vec![TorrentGetField::Totalsize],
+            TorrentFilter::IsPrivate => vec![TorrentGetField::Isprivate],
+            TorrentFilter::IsFinished => vec![TorrentGetField::Isfinished],
+            TorrentFilter::IsStalled => vec![TorrentGetField::Isstalled],
+            TorrentFilter::NameContains(_) => vec![TorrentGetField::Name],
+            TorrentFilter::All(filters) | TorrentFilter::Any(filters) => {
+                filters.iter().for_each(|it| it.collect_fields(fields));
+                vec![]
+            }
+            TorrentFilter::Not(filter) => {
+                filter.collect_fields(fields);
+                vec![]
+            }
+        };
+        add_fields(fields, needed);
+    }
+}
+
+/// Keys torrents can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Id,
+    Name,
+    Ratio,
+    TotalSize,
+    PercentDone,
+    AddedDate,
+    DoneDate,
+    ActivityDate,
+    RateDownload,
+    RateUpload,
+    QueuePosition,
+}
+
+impl SortKey {
+    fn field(&self) -> TorrentGetField {
+        match self {
+            SortKey::Id => TorrentGetField::Id,
+            SortKey::Name => TorrentGetField::Name,
+            SortKey::Ratio => TorrentGetField::Uploadratio,
+            SortKey::TotalSize => TorrentGetField::Totalsize,
+            SortKey::PercentDone => TorrentGetField::Percentdone,
+            SortKey::AddedDate => TorrentGetField::Addeddate,
+            SortKey::DoneDate => TorrentGetField::Donedate,
+            SortKey::ActivityDate => TorrentGetField::Activitydate,
+            SortKey::RateDownload => TorrentGetField::Ratedownload,
+            SortKey::RateUpload => TorrentGetField::Rateupload,
+            SortKey::QueuePosition => TorrentGetField::Queueposition,
+        }
+    }
+
+    fn compare(&self, a: &Torrent, b: &Torrent) -> Ordering {
+        fn by<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Ordering {
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        }
+        match self {
+            SortKey::Id => by(a.id, b.id),
+            SortKey::Name => by(a.name.as_ref(), b.name.as_ref()),
+            SortKey::Ratio => by(a.upload_ratio, b.upload_ratio),
+            SortKey::TotalSize => by(a.total_size, b.total_size),
+            SortKey::PercentDone => by(a.percent_done, b.percent_done),
+            SortKey::AddedDate => by(a.added_date, b.added_date),
+            SortKey::DoneDate => by(a.done_date, b.done_date),
+            SortKey::ActivityDate => by(a.activity_date, b.activity_date),
+            SortKey::RateDownload => by(a.rate_download, b.rate_download),
+            SortKey::RateUpload => by(a.rate_upload, b.rate_upload),
+            SortKey::QueuePosition => by(a.queue_position, b.queue_position),
+        }
+    }
+}
+
+/// Filters, sorts and limits a list of torrents
+///
+/// ```
+/// use transmission_rpc::filter::{SortKey, TorrentFilter, TorrentQuery};
+///
+/// // the 10 biggest torrents labelled "tv"
+/// let query = TorrentQuery::new()
+///     .filter(TorrentFilter::Label("tv".to_string()))
+///     .sort_by(SortKey::TotalSize, true)
+///     .limit(10);
+/// let fields = query.fields();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TorrentQuery {
+    pub filter: Option<TorrentFilter>,
+    /// sort keys in order of precedence, with `true` for descending order
+    pub sort: Vec<(SortKey, bool)>,
+    pub limit: Option<usize>,
+}
+
+impl TorrentQuery {
+    pub fn new() -> TorrentQuery {
+        TorrentQuery::default()
+    }
+
+    pub fn filter(mut self, filter: TorrentFilter) -> TorrentQuery {
+        self.filter = Some(match self.filter {
+            Some(it) => it.and(filter),
+            None => filter,
+        });
+        self
+    }
+
+    pub fn sort_by(mut self, key: SortKey, descending: bool) -> TorrentQuery {
+        self.sort.push((key, descending));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> TorrentQuery {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Fields to request with torrent-get for the query to work:
+    /// id, name and hash, plus the ones the filter and the sort keys look at
+    pub fn fields(&self) -> Vec<TorrentGetField> {
+        let mut fields = vec![TorrentGetField::Id, TorrentGetField::Name, TorrentGetField::HashString];
+        if let Some(filter) = &self.filter {
+            add_fields(&mut fields, filter.fields());
+        }
+        add_fields(&mut fields, self.sort.iter().map(|(key, _)| key.field()).collect());
+        fields
+    }
+
+    pub fn apply(&self, torrents: Vec<Torrent>) -> Vec<Torrent> {
+        let now = unix_now();
+        let mut torrents: Vec<Torrent> = torrents
+            .into_iter()
+            .filter(|it| match &self.filter {
+                Some(filter) => filter.matches_at(it, now),
+                None => true,
+            })
+            .collect();
+        torrents.sort_by(|a, b| {
+            self.sort
+                .iter()
+                .map(|(key, descending)| {
+                    let ordering = key.compare(a, b);
+                    if *descending { ordering.reverse() } else { ordering }
+                })
+                .find(|it| *it != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        if let Some(limit) = self.limit {
+            torrents.truncate(limit);
+        }
+        torrents
+    }
+
+    pub fn apply_to(&self, torrents: Torrents<Torrent>) -> Torrents<Torrent> {
+        Torrents {
+            torrents: self.apply(torrents.torrents),
+        }
+    }
+}
+
+fn add_fields(fields: &mut Vec<TorrentGetField>, needed: Vec<TorrentGetField>) {
+    for field in needed {
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+}
+
+/// Tells if `path` is `dir` or a path below it
+pub(crate) fn is_below(path: &str, dir: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 3600;
+
+    fn torrents() -> Vec<Torrent> {
+        serde_json::from_str(
+            r#"[
+                {"id": 1, "name": "Show S01", "status": 6, "uploadRatio": 2.5, "addedDate": 1000,
+                 "downloadDir": "/data/tv", "labels": ["tv"], "error": 0, "totalSize": 300,
+                 "trackers": [{"id": 0, "announce": "https://tracker.example.com/announce"}]},
+                {"id": 2, "name": "Movie", "status": 6, "uploadRatio": 0.5, "addedDate": 1000,
+                 "downloadDir": "/data/movies", "labels": [], "error": 0, "totalSize": 700},
+                {"id": 3, "name": "Show S02", "status": 0, "uploadRatio": 3.0, "addedDate": 9000000,
+                 "downloadDir": "/data/tv-old", "labels": ["tv"], "error": 3, "totalSize": 500}
+            ]"#,
+        ).unwrap()
+    }
+
+    fn ids(torrents: &[Torrent]) -> Vec<i64> {
+        torrents.iter().filter_map(|it| it.id).collect()
+    }
+
+    #[test]
+    pub fn test_filter() {
+        let now = 1000 + 31 * DAY as i64;
+        let torrents = torrents();
+        let old_seeds = TorrentFilter::Status(TorrentStatus::Seeding)
+            .and(TorrentFilter::RatioAtLeast(2.0))
+            .and(TorrentFilter::AddedOlderThan(Duration::from_secs(30 * DAY)));
+        let matching: Vec<Torrent> = torrents.iter().filter(|it| old_seeds.matches_at(it, now)).cloned().collect();
+        assert_eq!(ids(&matching), vec![1]);
+
+        let tv = TorrentFilter::DownloadDir("/data/tv/".to_string());
+        assert_eq!(ids(&torrents.iter().filter(|it| tv.matches(it)).cloned().collect::<Vec<_>>()), vec![1]);
+        assert!(TorrentFilter::HasError.matches(&torrents[2]));
+        assert!(TorrentFilter::TrackerHost("TRACKER.example.com".to_string()).matches(&torrents[0]));
+        assert!(TorrentFilter::Label("tv".to_string()).not().matches(&torrents[1]));
+        assert_eq!(
+            old_seeds.fields(),
+            vec![TorrentGetField::Status, TorrentGetField::Uploadratio, TorrentGetField::Addeddate]
+        );
+    }
+
+    #[test]
+    pub fn test_query() {
+        let query = TorrentQuery::new()
+            .filter(TorrentFilter::SizeAbove(400).or(TorrentFilter::Label("tv".to_string())))
+            .sort_by(SortKey::Ratio, true)
+            .limit(2);
+        assert_eq!(ids(&query.apply(torrents())), vec![3, 1]);
+        let fields = query.fields();
+        assert!(fields.contains(&TorrentGetField::Labels));
+        assert!(fields.contains(&TorrentGetField::Totalsize));
+        assert!(fields.contains(&TorrentGetField::Uploadratio));
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use futures::future::join_all;
+
+use crate::types::{Id, Nothing, Result, RpcResponse, SessionStats, Torrent, TorrentAction};
+use crate::types::{TorrentAddArgs, TorrentAdded, TorrentGetField, Torrents};
+use crate::TransClient;
+
+#[derive(Clone, Debug)]
+pub enum FleetError {
+    NoInstances,
+    UnknownInstance(String),
+    UnknownHash(String),
+    NotAHash(i64),
+}
+
+impl std::fmt::Display for FleetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FleetError::NoInstances => write!(f, "No instances in the fleet!"),
+            FleetError::UnknownInstance(name) => write!(f, "Unknown instance: {}!", name),
+            FleetError::UnknownHash(hash) => write!(f, "No instance owns torrent {}!", hash),
+            FleetError::NotAHash(id) => {
+                write!(f, "Torrent id {} is ambiguous in a fleet, use its hash!", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FleetError {}
+
+/// A value coming from the instance called `instance`
+#[derive(Debug)]
+pub struct Tagged<T> {
+    pub instance: String,
+    pub value: T,
+}
+
+/// How the load of an instance is measured when picking where a torrent is added
+#[derive(Clone, Debug)]
+pub enum LoadMetric {
+    /// the instance with the most free space in its default download dir
+    FreeSpace,
+    /// the instance with the fewest active torrents
+    ActiveTorrents,
+    /// the instance with the lowest download + upload speed
+    Speed,
+}
+
+/// Several named Transmission daemons managed together
+#[derive(Default)]
+pub struct TransFleet {
+    clients: Vec<(String, TransClient)>,
+    /// torrent hash -> name of the instance which has it
+    owners: HashMap<String, String>,
+}
+
+impl TransFleet {
+    pub fn new() -> TransFleet {
+        TransFleet::default()
+    }
+
+    /// Adds a client under `name`, replacing any client which had the same name
+    pub fn add(&mut self, name: &str, client: TransClient) {
+        self.clients.retain(|(it, _)| it != name);
+        self.owners.retain(|_, owner| owner != name);
+        self.clients.push((name.to_string(), client));
+    }
+
+    /// Names of all instances, in the order they were added
+    pub fn names(&self) -> Vec<&str> {
+        self.clients.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Returns the client called `name`
+    pub fn client(&mut self, name: &str) -> Option<&mut TransClient> {
+        self.clients
+            .iter_mut()
+            .find(|(it, _)| it == name)
+            .map(|(_, client)| client)
+    }
+
+    /// Returns the name of the instance known to have the torrent with `hash`
+    pub fn owner(&self, hash: &str) -> Option<&str> {
+        self.owners.get(&hash.to_lowercase()).map(|it| it.as_str())
+    }
+
+    /// Performs a torrent get call on every instance.
+    /// Each response is tagged with the name of the instance it comes from.
+    ///
+    /// The hashes of received torrents are remembered,
+    /// so that later actions are routed to the instance owning them.
+    pub async fn torrent_get(
+        &mut self,
+        fields: Option<Vec<TorrentGetField>>,
+        ids: Option<Vec<Id>>,
+    ) -> Vec<Tagged<Result<RpcResponse<Torrents<Torrent>>>>> {
+        let responses = join_all(self.clients.iter_mut().map(|(name, client)| {
+            let fields = fields.clone();
+            let ids = ids.clone();
+            async move {
+                Tagged {
+                    instance: name.clone(),
+                    value: client.torrent_get(fields, ids).await,
+                }
+            }
+        }))
+        .await;
+        for response in &responses {
+            if let Ok(it) = &response.value {
+                self.remember(&response.instance, &it.arguments.torrents);
+            }
+        }
+        responses
+    }
+
+    /// Performs a torrent get call on every instance and returns the torrents of each one.
+    /// An instance which fails to answer only has its own error,
+    /// the torrents of the other instances are still returned.
+    pub async fn torrents(
+        &mut self,
+        fields: Option<Vec<TorrentGetField>>,
+        ids: Option<Vec<Id>>,
+    ) -> Vec<Tagged<Result<Vec<Torrent>>>> {
+        self.torrent_get(fields, ids)
+            .await
+            .into_iter()
+            .map(|response| Tagged {
+                instance: response.instance,
+                value: response.value.map(|it| it.arguments.torrents),
+            })
+            .collect()
+    }
+
+    /// Performs a session stats call on every instance
+    pub async fn session_stats(&mut self) -> Vec<Tagged<Result<RpcResponse<SessionStats>>>> {
+        join_all(self.clients.iter_mut().map(|(name, client)| async move {
+            Tagged {
+                instance: name.clone(),
+                value: client.session_stats().await,
+            }
+        }))
+        .await
+    }
+
+    /// Returns the name of the least loaded instance according to `metric`.
+    /// Instances which fail to answer are skipped.
+    ///
+    /// # Errors
+    ///
+    /// `FleetError::NoInstances` if no instance answered
+    pub async fn least_loaded(&mut self, metric: LoadMetric) -> Result<String> {
+        let loads = join_all(self.clients.iter_mut().map(|(name, client)| {
+            let metric = metric.clone();
+            async move { (name.clone(), load(client, metric).await) }
+        }))
+        .await;
+        loads
+            .into_iter()
+            .filter_map(|(name, load)| match load {
+                Ok(load) => Some((name, load)),
+                Err(e) => {
+                    warn!("Unable to get the load of {}: {}", name, e);
+                    None
+                }
+            })
+            .min_by_key(|(_, load)| *load)
+            .map(|(name, _)| name)
+            .ok_or_else(|| From::from(FleetError::NoInstances))
+    }
+
+    /// Adds a torrent to the least loaded instance according to `metric`.
+    /// A torrent already present on that instance is remembered as owned by it,
+    /// and is found in `torrent_duplicate` of the response.
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error, or `FleetError::NoInstances`
+    pub async fn torrent_add(
+        &mut self,
+        metric: LoadMetric,
+        add: TorrentAddArgs,
+    ) -> Result<Tagged<RpcResponse<TorrentAdded>>> {
+        let instance = self.least_loaded(metric).await?;
+        let response = self.instance(&instance)?.torrent_add(add).await?;
+        let arguments = &response.arguments;
+        if let Some(hash) = arguments
+            .torrent_added
+            .as_ref()
+            .or(arguments.torrent_duplicate.as_ref())
+            .and_then(|it| it.hash_string.as_ref())
+        {
+            self.owners.insert(hash.to_lowercase(), instance.clone());
+        }
+        Ok(Tagged {
+            instance,
+            value: response,
+        })
+    }
+
+    /// Performs a torrent action call on the instances owning the torrents.
+    /// `ids` must be hashes, since numeric ids are only unique within one instance.
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error, or a `FleetError` when a torrent cannot be routed
+    pub async fn torrent_action(
+        &mut self,
+        action: TorrentAction,
+        ids: Vec<Id>,
+    ) -> Result<Vec<Tagged<RpcResponse<Nothing>>>> {
+        let mut responses = vec![];
+        for (instance, ids) in self.route(ids).await? {
+            let value = self.instance(&instance)?.torrent_action(action.clone(), ids).await?;
+            responses.push(Tagged { instance, value });
+        }
+        Ok(responses)
+    }
+
+    /// Performs a torrent remove call on the instances owning the torrents.
+    /// `ids` must be hashes, since numeric ids are only unique within one instance.
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error, or a `FleetError` when a torrent cannot be routed
+    pub async fn torrent_remove(
+        &mut self,
+        ids: Vec<Id>,
+        delete_local_data: bool,
+    ) -> Result<Vec<Tagged<RpcResponse<Nothing>>>> {
+        let mut responses = vec![];
+        for (instance, ids) in self.route(ids).await? {
+            let hashes: Vec<String> = ids.iter().filter_map(hash_of).collect();
+            let value = self.instance(&instance)?.torrent_remove(ids, delete_local_data).await?;
+            if value.is_ok() {
+                self.owners.retain(|hash, _| !hashes.contains(hash));
+            }
+            responses.push(Tagged { instance, value });
+        }
+        Ok(responses)
+    }
+
+    /// Groups `ids` by the instance owning them.
+    /// Owners are looked up again on all instances if some hash is unknown.
+    async fn route(&mut self, ids: Vec<Id>) -> Result<Vec<(String, Vec<Id>)>> {
+        let mut hashes = vec![];
+        for id in &ids {
+            match id {
+                Id::Id(id) => return Err(From::from(FleetError::NotAHash(*id))),
+                Id::Hash(hash) => hashes.push(hash.to_lowercase()),
+            }
+        }
+        if hashes.iter().any(|hash| !self.owners.contains_key(hash)) {
+            self.torrent_get(Some(vec![TorrentGetField::HashString]), None).await;
+        }
+        let mut routes: Vec<(String, Vec<Id>)> = vec![];
+        for hash in hashes {
+            let owner = self
+                .owners
+                .get(&hash)
+                .ok_or_else(|| FleetError::UnknownHash(hash.clone()))?;
+            match routes.iter_mut().find(|(instance, _)| instance == owner) {
+                Some((_, ids)) => ids.push(Id::Hash(hash)),
+                None => routes.push((owner.clone(), vec![Id::Hash(hash)])),
+            }
+        }
+        Ok(routes)
+    }
+
+    fn instance(&mut self, name: &str) -> Result<&mut TransClient> {
+        self.client(name)
+            .ok_or_else(|| From::from(FleetError::UnknownInstance(name.to_string())))
+    }
+
+    fn remember(&mut self, instance: &str, torrents: &[Torrent]) {
+        for hash in torrents.iter().filter_map(|it| it.hash_string.as_ref()) {
+            self.owners.insert(hash.to_lowercase(), instance.to_string());
+        }
+    }
+}
+
+fn hash_of(id: &Id) -> Option<String> {
+    match id {
+        Id::Id(_) => None,
+        Id::Hash(hash) => Some(hash.to_lowercase()),
+    }
+}
+
+/// Returns the load of an instance, lower is less loaded
+async fn load(client: &mut TransClient, metric: LoadMetric) -> Result<i64> {
+    match metric {
+        LoadMetric::FreeSpace => {
+            let download_dir = client.session_get().await?.arguments.download_dir;
+            let free_space = client.free_space(download_dir).await?.arguments.size_bytes;
+            Ok(-free_space)
+        }
+        LoadMetric::ActiveTorrents => {
+            let stats = client.session_stats().await?.arguments;
+            Ok(stats.active_torrent_count as i64)
+        }
+        LoadMetric::Speed => {
+            let stats = client.session_stats().await?.arguments;
+            Ok(stats.download_speed + stats.upload_speed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent(hash: &str) -> Torrent {
+        serde_json::from_str(&format!("{{\"hashString\": \"{}\"}}", hash)).unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn test_route_groups_hashes_by_owner() -> Result<()> {
+        let mut fleet = TransFleet::new();
+        fleet.add("a", TransClient::new("http://a:9091/transmission/rpc"));
+        fleet.add("b", TransClient::new("http://b:9091/transmission/rpc"));
+        fleet.remember("a", &[torrent("aaa"), torrent("AAB")]);
+        fleet.remember("b", &[torrent("bbb")]);
+
+        let routes = fleet
+            .route(vec![
+                Id::Hash("aab".to_string()),
+                Id::Hash("bbb".to_string()),
+                Id::Hash("aaa".to_string()),
+            ])
+            .await?;
+        assert_eq!(
+            routes,
+            vec![
+                ("a".to_string(), vec![Id::Hash("aab".to_string()), Id::Hash("aaa".to_string())]),
+                ("b".to_string(), vec![Id::Hash("bbb".to_string())]),
+            ]
+        );
+        assert!(fleet.route(vec![Id::Id(1)]).await.is_err());
+        Ok(())
+    }
+}
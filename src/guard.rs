@@ -0,0 +1,295 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::types::{Id, Result, Torrent, TorrentAction, TorrentGetField, TorrentStatus};
+use crate::TransClient;
+
+/// Stops downloads when the space they still need would bring a download dir
+/// below a free space threshold, and starts them again once there is room.
+///
+/// Only the torrents stopped by the guard are started again,
+/// torrents stopped by someone else are left alone.
+#[derive(Debug, Clone)]
+pub struct DiskGuard {
+    /// free space to keep in every download dir, in bytes
+    pub min_free_bytes: i64,
+    /// extra free space needed before a stopped download is started again,
+    /// so that torrents are not stopped and started on every check
+    pub resume_margin_bytes: i64,
+    /// hashes of the torrents stopped by the guard
+    stopped: HashSet<String>,
+}
+
+/// Space of one download dir, as seen by the last check
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirSpace {
+    pub download_dir: String,
+    pub free_bytes: i64,
+    /// bytes still to download by the running downloads in the dir
+    pub needed_bytes: i64,
+}
+
+impl DirSpace {
+    /// Free space once all running downloads are complete
+    pub fn projected_free_bytes(&self) -> i64 {
+        self.free_bytes - self.needed_bytes
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GuardReport {
+    pub dirs: Vec<DirSpace>,
+    pub stopped: Vec<Id>,
+    pub started: Vec<Id>,
+}
+
+impl DiskGuard {
+    pub fn new(min_free_bytes: i64, resume_margin_bytes: i64) -> DiskGuard {
+        DiskGuard {
+            min_free_bytes,
+            resume_margin_bytes,
+            stopped: HashSet::new(),
+        }
+    }
+
+    /// Hashes of the torrents currently stopped by the guard
+    pub fn stopped(&self) -> &HashSet<String> {
+        &self.stopped
+    }
+
+    /// Checks the free space of every download dir, then stops or starts downloads
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    pub async fn check(&mut self, client: &mut TransClient) -> Result<GuardReport> {
+        let fields = vec![
+            TorrentGetField::Id,
+            TorrentGetField::HashString,
+            TorrentGetField::Name,
+            TorrentGetField::Status,
+            TorrentGetField::Downloaddir,
+            TorrentGetField::Leftuntildone,
+            TorrentGetField::Bandwidthpriority,
+            TorrentGetField::Queueposition,
+        ];
+        let response = client.torrent_get(Some(fields), None).await?;
+        if !response.is_ok() {
+            return Err(From::from(format!("torrent-get failed: {}", response.result)));
+        }
+        let torrents = response.arguments.torrents;
+        // forget the torrents removed since they were stopped
+        self.stopped.retain(|hash| {
+            torrents
+                .iter()
+                .any(|it| it.hash_string.as_ref().is_some_and(|it| it.eq_ignore_ascii_case(hash)))
+        });
+
+        let mut free_bytes = HashMap::new();
+        for dir in torrents.iter().filter_map(|it| it.download_dir.as_ref()) {
+            if !free_bytes.contains_key(dir) {
+                let free_space = client.free_space(dir.clone()).await?;
+                free_bytes.insert(dir.clone(), free_space.arguments.size_bytes);
+            }
+        }
+
+        let mut report = self.plan(&torrents, &free_bytes);
+        if !report.stopped.is_empty() {
+            let response = client.torrent_action(TorrentAction::Stop, report.stopped.clone()).await?;
+            if response.is_ok() {
+                self.stopped.extend(report.stopped.iter().filter_map(hash_of));
+            } else {
+                warn!("Unable to stop downloads: {}", response.result);
+                report.stopped.clear();
+            }
+        }
+        if !report.started.is_empty() {
+            let response = client.torrent_action(TorrentAction::Start, report.started.clone()).await?;
+            if response.is_ok() {
+                for hash in report.started.iter().filter_map(hash_of) {
+                    self.stopped.remove(&hash);
+                }
+            } else {
+                warn!("Unable to start downloads: {}", response.result);
+                report.started.clear();
+            }
+        }
+        Ok(report)
+    }
+
+    /// Runs `check` every `interval`, logging the free and needed space of each download dir
+    ///
+    /// # Errors
+    ///
+    /// The error of the first check that fails, the torrents it stopped stay stopped
+    pub async fn watch(&mut self, client: &mut TransClient, interval: Duration) -> Result<()> {
+        loop {
+            let report = self.check(client).await?;
+            for dir in &report.dirs {
+                debug!(
+                    "{}: {} bytes free, {} bytes needed",
+                    dir.download_dir, dir.free_bytes, dir.needed_bytes
+                );
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Decides which downloads to stop and start, given the free space of each download dir
+    fn plan(&self, torrents: &[Torrent], free_bytes: &HashMap<String, i64>) -> GuardReport {
+        let mut report = GuardReport::default();
+        let mut dirs: Vec<&String> = free_bytes.keys().collect();
+        dirs.sort();
+        for dir in dirs {
+            let in_dir: Vec<&Torrent> = torrents
+                .iter()
+                .filter(|it| it.download_dir.as_ref() == Some(dir) && left(it) > 0)
+                .collect();
+            // running downloads, the ones to stop first at the end
+            let mut running: Vec<&Torrent> = in_dir
+                .iter()
+                .filter(|it| is_downloading(it))
+                .copied()
+                .collect();
+            running.sort_by_key(|it| priority(it));
+            // downloads stopped by the guard, the ones to start first at the beginning
+            let mut stopped: Vec<&Torrent> = in_dir
+                .iter()
+                .filter(|it| it.torrent_status() == Some(TorrentStatus::Stopped) && self.is_stopped_by_guard(it))
+                .copied()
+                .collect();
+            stopped.sort_by_key(|it| std::cmp::Reverse(priority(it)));
+
+            let mut space = DirSpace {
+                download_dir: dir.clone(),
+                free_bytes: free_bytes[dir],
+                needed_bytes: running.iter().map(|it| left(it)).sum(),
+            };
+            let mut running = running.into_iter();
+            let mut stopped_here = false;
+            while space.projected_free_bytes() < self.min_free_bytes {
+                match running.next() {
+                    Some(torrent) => {
+                        space.needed_bytes -= left(torrent);
+                        report.stopped.extend(id_of(torrent));
+                        stopped_here = true;
+                    }
+                    None => break,
+                }
+            }
+            if !stopped_here {
+                for torrent in stopped {
+                    if space.projected_free_bytes() - left(torrent) < self.min_free_bytes + self.resume_margin_bytes {
+                        break;
+                    }
+                    space.needed_bytes += left(torrent);
+                    report.started.extend(id_of(torrent));
+                }
+            }
+            report.dirs.push(space);
+        }
+        report
+    }
+
+    fn is_stopped_by_guard(&self, torrent: &Torrent) -> bool {
+        torrent
+            .hash_string
+            .as_ref()
+            .is_some_and(|it| self.stopped.contains(&it.to_lowercase()))
+    }
+}
+
+fn is_downloading(torrent: &Torrent) -> bool {
+    matches!(
+        torrent.torrent_status(),
+        Some(TorrentStatus::Downloading) | Some(TorrentStatus::QueuedToDownload)
+    )
+}
+
+fn left(torrent: &Torrent) -> i64 {
+    torrent.left_until_done.unwrap_or(0)
+}
+
+/// Ordering key of a download, the lowest one is the least important:
+/// low bandwidth priority first, then the last in the queue
+fn priority(torrent: &Torrent) -> (i64, i64) {
+    (
+        torrent.bandwidth_priority.unwrap_or(0),
+        -torrent.queue_position.unwrap_or(0),
+    )
+}
+
+fn id_of(torrent: &Torrent) -> Option<Id> {
+    match (&torrent.hash_string, torrent.id) {
+        (Some(hash), _) => Some(Id::Hash(hash.to_lowercase())),
+        (None, Some(id)) => Some(Id::Id(id)),
+        _ => None,
+    }
+}
+
+fn hash_of(id: &Id) -> Option<String> {
+    match id {
+        Id::Hash(hash) => Some(hash.clone()),
+        Id::Id(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrents() -> Vec<Torrent> {
+        serde_json::from_str(
+            r#"[
+                {"id": 1, "hashString": "a", "status": 4, "downloadDir": "/data", "leftUntilDone": 40,
+                 "bandwidthPriority": 0, "queuePosition": 0},
+                {"id": 2, "hashString": "b", "status": 4, "downloadDir": "/data", "leftUntilDone": 30,
+                 "bandwidthPriority": 0, "queuePosition": 1},
+                {"id": 3, "hashString": "c", "status": 4, "downloadDir": "/data", "leftUntilDone": 20,
+                 "bandwidthPriority": 1, "queuePosition": 2},
+                {"id": 4, "hashString": "d", "status": 6, "downloadDir": "/data", "leftUntilDone": 0}
+            ]"#,
+        ).unwrap()
+    }
+
+    #[test]
+    pub fn test_stops_least_important_downloads() {
+        let guard = DiskGuard::new(50, 10);
+        let free = vec![("/data".to_string(), 120)].into_iter().collect();
+        let report = guard.plan(&torrents(), &free);
+        // 120 free - 90 needed < 50: stop "b" (last in queue), then 120 - 60 >= 50
+        assert_eq!(report.stopped, vec![Id::Hash("b".to_string())]);
+        assert_eq!(report.dirs[0].projected_free_bytes(), 60);
+        assert!(report.started.is_empty());
+    }
+
+    #[test]
+    pub fn test_starts_downloads_stopped_by_guard() {
+        let mut guard = DiskGuard::new(50, 10);
+        guard.stopped.insert("b".to_string());
+        guard.stopped.insert("a".to_string());
+        let mut torrents = torrents();
+        torrents[0].status = Some(0);
+        torrents[1].status = Some(0);
+        let free = vec![("/data".to_string(), 130)].into_iter().collect();
+        let report = guard.plan(&torrents, &free);
+        // 130 - 20 = 110, starting "a" leaves 70 >= 60, then starting "b" would leave 40
+        assert_eq!(report.started, vec![Id::Hash("a".to_string())]);
+        assert!(report.stopped.is_empty());
+    }
+
+    #[test]
+    pub fn test_dirs_are_planned_separately() {
+        let mut guard = DiskGuard::new(50, 10);
+        guard.stopped.insert("e".to_string());
+        let mut torrents = torrents();
+        torrents.extend(serde_json::from_str::<Vec<Torrent>>(
+            r#"[{"id": 5, "hashString": "e", "status": 0, "downloadDir": "/other", "leftUntilDone": 10}]"#,
+        ).unwrap());
+        let free = vec![("/data".to_string(), 120), ("/other".to_string(), 1000)].into_iter().collect();
+        let report = guard.plan(&torrents, &free);
+        // "/data" is short of space, "/other" has room to start its download again
+        assert_eq!(report.stopped, vec![Id::Hash("b".to_string())]);
+        assert_eq!(report.started, vec![Id::Hash("e".to_string())]);
+    }
+}
@@ -3,37 +3,81 @@ extern crate env_logger;
 extern crate log;
 extern crate reqwest;
 
-use reqwest::header::CONTENT_TYPE;
-use reqwest::{Client, StatusCode};
+use std::sync::Mutex;
+
+use futures::stream::{self, StreamExt};
+use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
 
+#[cfg(feature = "cli")]
+pub mod config;
+#[cfg(feature = "exporter")]
+pub mod exporter;
+#[cfg(feature = "feed")]
+pub mod feed;
+pub mod filter;
+pub mod fleet;
+pub mod guard;
+#[cfg(feature = "notifier")]
+pub mod notifier;
+pub mod policy;
+pub mod relocate;
+#[cfg(feature = "rename")]
+pub mod rename;
+mod retry;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+#[cfg(any(feature = "feed", feature = "notifier", feature = "scheduler"))]
+mod state;
+mod transport;
 pub mod types;
+pub mod verify;
+pub mod watch;
+
+pub use retry::RetryPolicy;
+use retry::Failure;
+use transport::Transport;
 
 use types::BasicAuth;
 use types::BlocklistUpdate;
 use types::SessionGet;
 use types::SessionStats;
 use types::SessionClose;
+use types::SessionSetArgs;
 use types::PortTest;
 use types::FreeSpace;
 use types::TorrentAction;
 use types::{Id, Torrent, TorrentGetField, Torrents};
 use types::{Nothing, Result, RpcRequest, RpcResponse, RpcResponseArgument, TorrentRenamePath};
-use types::{TorrentAddArgs, TorrentAdded};
+use types::{TorrentAddArgs, TorrentAdded, TorrentSetArgs, TrackerList};
 
 const MAX_RETRIES: usize = 5;
+const ID_CHUNK_SIZE: usize = 1000;
 
 #[derive(Clone, Debug)]
-enum TransError {
+pub enum TransError {
     MaxRetriesReached,
     NoSessionIdReceived,
+    ServerError(StatusCode),
+    /// the daemon's rpc-version is too old for the request
+    Unsupported {
+        method: String,
+        required: i32,
+        available: i32,
+    },
 }
 
 impl std::fmt::Display for TransError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
+        match self {
             TransError::MaxRetriesReached => write!(f, "Max retries reached!"),
             TransError::NoSessionIdReceived => write!(f, "No session id received!"),
+            TransError::ServerError(status) => write!(f, "Server responded with {}!", status),
+            TransError::Unsupported { method, required, available } => write!(
+                f,
+                "Unsupported: {} requires rpc-version {}, but the daemon has {}!",
+                method, required, available
+            ),
         }
     }
 }
@@ -41,41 +85,56 @@ impl std::fmt::Display for TransError {
 impl std::error::Error for TransError {}
 
 pub struct TransClient {
-    url: String,
+    transport: Transport,
     auth: Option<BasicAuth>,
-    session_id: Option<String>,
-    client: Client,
+    session_id: Mutex<Option<String>>,
+    rpc_version: Mutex<Option<i32>>,
+    id_chunk_size: usize,
+    retry_policy: RetryPolicy,
 }
 
 impl TransClient {
-    /// Returns HTTP(S) client with configured Basic Auth
+    /// Returns client with configured Basic Auth
+    ///
+    /// `url` is either an HTTP(S) url or a `unix:/path/to/socket` of a daemon
+    /// listening on a Unix socket
     pub fn with_auth(url: &str, basic_auth: BasicAuth) -> TransClient {
         TransClient {
-            url: url.to_string(),
+            transport: Transport::new(url),
             auth: Some(basic_auth),
-            session_id: None,
-            client: Client::new(),
+            session_id: Mutex::new(None),
+            rpc_version: Mutex::new(None),
+            id_chunk_size: ID_CHUNK_SIZE,
+            retry_policy: RetryPolicy::default(),
         }
     }
 
-    /// Returns HTTP(S) client
+    /// Returns client
+    ///
+    /// `url` is either an HTTP(S) url or a `unix:/path/to/socket` of a daemon
+    /// listening on a Unix socket
     pub fn new(url: &str) -> TransClient {
         TransClient {
-            url: url.to_string(),
+            transport: Transport::new(url),
             auth: None,
-            session_id: None,
-            client: Client::new(),
+            session_id: Mutex::new(None),
+            rpc_version: Mutex::new(None),
+            id_chunk_size: ID_CHUNK_SIZE,
+            retry_policy: RetryPolicy::default(),
         }
     }
 
-    /// Prepares a request for provided server and auth
-    fn rpc_request(&self) -> reqwest::RequestBuilder {
-        if let Some(auth) = &self.auth {
-            self.client.post(&self.url)
-                .basic_auth(&auth.user, Some(&auth.password))
-        } else {
-            self.client.post(&self.url)
-        }.header(CONTENT_TYPE, "application/json")
+    /// Sets the maximum number of ids sent in a single request.
+    /// Longer id lists given to `torrent_get`, `torrent_action` and `torrent_remove`
+    /// are split into several requests and their results are merged. A failing request
+    /// stops the ones after it, but the requests before it have been applied.
+    pub fn set_id_chunk_size(&mut self, chunk_size: usize) {
+        self.id_chunk_size = chunk_size.max(1);
+    }
+
+    /// Sets how calls failing with a transient error are retried
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
     }
 
     /// Performs a session get call
@@ -111,7 +170,55 @@ impl TransClient {
     /// }
     /// ```
     pub async fn session_get(&mut self) -> Result<RpcResponse<SessionGet>> {
-        self.call(RpcRequest::session_get()).await
+        self.fetch_session().await
+    }
+
+    /// Returns the rpc-version of the daemon.
+    /// It is fetched with a session get call once and cached until the daemon hands out a new session id.
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    pub async fn rpc_version(&mut self) -> Result<i32> {
+        self.negotiated_rpc_version().await
+    }
+
+    /// Performs a session set call
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{SessionSetArgs, Nothing};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let args = SessionSetArgs {
+    ///         alt_speed_enabled: Some(true),
+    ///         ..SessionSetArgs::default()
+    ///     };
+    ///     let res: RpcResponse<Nothing> = client.session_set(args).await?;
+    ///     println!("Session set result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn session_set(&mut self, args: SessionSetArgs) -> Result<RpcResponse<Nothing>> {
+        self.call(RpcRequest::session_set(args)).await
     }
 
     /// Performs a session stats call
@@ -296,12 +403,15 @@ impl TransClient {
     }
 
     /// Performs a torrent get call
-    /// fileds - if None then ALL fields
+    /// fileds - if None then ALL fields known to the daemon
     /// ids - if None then All items
     ///
+    /// Fields which are too new for the daemon's rpc-version are not requested
+    ///
     /// # Errors
     ///
-    /// Any IO Error or Deserialization error
+    /// Any IO Error or Deserialization error,
+    /// or `TransError::Unsupported` if none of the fields is known to the daemon's rpc-version
     ///
     /// # Example
     ///
@@ -350,11 +460,25 @@ impl TransClient {
         fields: Option<Vec<TorrentGetField>>,
         ids: Option<Vec<Id>>,
     ) -> Result<RpcResponse<Torrents<Torrent>>> {
-        self.call(RpcRequest::torrent_get(fields, ids)).await
+        let rpc_version = self.negotiated_rpc_version().await?;
+        let fields = Some(supported_fields(fields, rpc_version)?);
+        match ids {
+            None => self.call(RpcRequest::torrent_get(fields, None)).await,
+            Some(ids) => {
+                self.call_chunked(
+                    ids,
+                    |chunk| RpcRequest::torrent_get(fields.clone(), Some(chunk)),
+                    |merged: &mut Torrents<Torrent>, next| merged.torrents.extend(next.torrents),
+                ).await
+            }
+        }
     }
 
     /// Performs a torrent action call
     ///
+    /// Ids beyond the id chunk size are sent in several calls, if one is not successful its response
+    /// is returned and the following chunks are not sent, while the earlier ones were already applied.
+    ///
     /// # Errors
     ///
     /// Any IO Error or Deserialization error
@@ -390,11 +514,18 @@ impl TransClient {
         action: TorrentAction,
         ids: Vec<Id>,
     ) -> Result<RpcResponse<Nothing>> {
-        self.call(RpcRequest::torrent_action(action, ids)).await
+        self.call_chunked(
+            ids,
+            |chunk| RpcRequest::torrent_action(action.clone(), chunk),
+            |_, _| {},
+        ).await
     }
 
     /// Performs a torrent remove call
     ///
+    /// Ids beyond the id chunk size are sent in several calls, if one is not successful its response
+    /// is returned and the following chunks are not sent, while the earlier ones were already applied.
+    ///
     /// # Errors
     ///
     /// Any IO Error or Deserialization error
@@ -428,8 +559,11 @@ impl TransClient {
         ids: Vec<Id>,
         delete_local_data: bool,
     ) -> Result<RpcResponse<Nothing>> {
-        self.call(RpcRequest::torrent_remove(ids, delete_local_data))
-            .await
+        self.call_chunked(
+            ids,
+            |chunk| RpcRequest::torrent_remove(chunk, delete_local_data),
+            |_, _| {},
+        ).await
     }
 
     /// Performs a torrent set location call
@@ -512,6 +646,430 @@ impl TransClient {
             .await
     }
 
+    /// Performs a torrent set call
+    /// ids - if None then All items
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{TorrentSetArgs, Nothing, Id};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let args = TorrentSetArgs {
+    ///         labels: Some(vec![String::from("tv")]),
+    ///         ..TorrentSetArgs::default()
+    ///     };
+    ///     let res: RpcResponse<Nothing> = client.torrent_set(args, Some(vec![Id::Id(1)])).await?;
+    ///     println!("Set result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn torrent_set(
+        &mut self,
+        args: TorrentSetArgs,
+        ids: Option<Vec<Id>>,
+    ) -> Result<RpcResponse<Nothing>> {
+        self.call(RpcRequest::torrent_set(args, ids)).await
+    }
+
+    /// Replaces the labels of the torrents
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing, Id};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let res: RpcResponse<Nothing> = client.set_labels(vec![Id::Id(1)], vec![String::from("tv")]).await?;
+    ///     println!("Set labels result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn set_labels(&mut self, ids: Vec<Id>, labels: Vec<String>) -> Result<RpcResponse<Nothing>> {
+        let args = TorrentSetArgs {
+            labels: Some(labels),
+            ..TorrentSetArgs::default()
+        };
+        self.torrent_set(args, Some(ids)).await
+    }
+
+    /// Adds labels to the torrents, keeping the labels they already have
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing, Id};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let res: RpcResponse<Nothing> = client.add_labels(vec![Id::Id(1)], vec![String::from("hd")]).await?;
+    ///     println!("Add labels result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn add_labels(&mut self, ids: Vec<Id>, labels: Vec<String>) -> Result<RpcResponse<Nothing>> {
+        self.update_labels(ids, |current| {
+            for label in &labels {
+                if !current.contains(label) {
+                    current.push(label.clone());
+                }
+            }
+        }).await
+    }
+
+    /// Removes labels from the torrents, keeping their other labels
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing, Id};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let res: RpcResponse<Nothing> = client.remove_labels(vec![Id::Id(1)], vec![String::from("hd")]).await?;
+    ///     println!("Remove labels result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn remove_labels(&mut self, ids: Vec<Id>, labels: Vec<String>) -> Result<RpcResponse<Nothing>> {
+        self.update_labels(ids, |current| current.retain(|it| !labels.contains(it))).await
+    }
+
+    /// Adds announce URLs to the tier `tier` (starting from 0) of the torrents,
+    /// or to a new tier if `tier` is None.
+    /// Daemons older than rpc-version 17 always add each URL in a new tier.
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing, Id};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let urls = vec![String::from("https://tracker.example.com/announce")];
+    ///     let res: RpcResponse<Nothing> = client.add_trackers(vec![Id::Id(1)], Some(0), urls).await?;
+    ///     println!("Add trackers result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn add_trackers(
+        &mut self,
+        ids: Vec<Id>,
+        tier: Option<usize>,
+        urls: Vec<String>,
+    ) -> Result<RpcResponse<Nothing>> {
+        if self.rpc_version().await? >= 17 {
+            self.update_tracker_lists(Some(ids), |list| list.add(tier, &urls)).await
+        } else {
+            let args = TorrentSetArgs {
+                tracker_add: Some(urls),
+                ..TorrentSetArgs::default()
+            };
+            self.torrent_set(args, Some(ids)).await
+        }
+    }
+
+    /// Removes the tracker with `tracker_id`, as found in `Torrent.trackers`, from the torrents
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing, Id};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let res: RpcResponse<Nothing> = client.remove_tracker(vec![Id::Id(1)], 0).await?;
+    ///     println!("Remove tracker result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn remove_tracker(&mut self, ids: Vec<Id>, tracker_id: i64) -> Result<RpcResponse<Nothing>> {
+        let args = TorrentSetArgs {
+            tracker_remove: Some(vec![tracker_id]),
+            ..TorrentSetArgs::default()
+        };
+        self.torrent_set(args, Some(ids)).await
+    }
+
+    /// Replaces the announce URL `old_url` with `new_url` in all torrents having it,
+    /// e.g. to change the passkey of a private tracker
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let res: RpcResponse<Nothing> = client.replace_tracker_url(
+    ///         "https://tracker.example.com/announce/old-passkey",
+    ///         "https://tracker.example.com/announce/new-passkey",
+    ///     ).await?;
+    ///     println!("Replace tracker result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn replace_tracker_url(&mut self, old_url: &str, new_url: &str) -> Result<RpcResponse<Nothing>> {
+        if self.rpc_version().await? >= 17 {
+            return self.update_tracker_lists(None, |list| list.replace(old_url, new_url)).await;
+        }
+        self.update_torrents(
+            None,
+            TorrentGetField::Trackers,
+            |torrent| {
+                torrent.trackers.iter().flatten()
+                    .filter(|it| it.announce == old_url)
+                    .map(|it| (it.id as i64, it.announce.clone()))
+                    .collect::<Vec<(i64, String)>>()
+            },
+            |replace| {
+                for (_, url) in replace.iter_mut() {
+                    *url = new_url.to_string();
+                }
+            },
+            |replace| TorrentSetArgs {
+                tracker_replace: Some(replace),
+                ..TorrentSetArgs::default()
+            },
+        ).await
+    }
+
+    /// Replaces all trackers of the torrents, needs rpc-version 17+
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing, Id, TrackerList};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let list = TrackerList::from(String::from("https://a.example.com/announce\n\nhttps://b.example.com/announce"));
+    ///     let res: RpcResponse<Nothing> = client.set_tracker_list(vec![Id::Id(1)], list).await?;
+    ///     println!("Set tracker list result: {:?}", &res.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn set_tracker_list(&mut self, ids: Vec<Id>, tracker_list: TrackerList) -> Result<RpcResponse<Nothing>> {
+        let args = TorrentSetArgs {
+            tracker_list: Some(tracker_list),
+            ..TorrentSetArgs::default()
+        };
+        self.torrent_set(args, Some(ids)).await
+    }
+
+    async fn update_labels<F>(&mut self, ids: Vec<Id>, update: F) -> Result<RpcResponse<Nothing>>
+        where
+            F: Fn(&mut Vec<String>),
+    {
+        self.update_torrents(
+            Some(ids),
+            TorrentGetField::Labels,
+            |torrent| torrent.labels.clone().unwrap_or_default(),
+            update,
+            |labels| TorrentSetArgs {
+                labels: Some(labels),
+                ..TorrentSetArgs::default()
+            },
+        ).await
+    }
+
+    async fn update_tracker_lists<F>(&mut self, ids: Option<Vec<Id>>, update: F) -> Result<RpcResponse<Nothing>>
+        where
+            F: Fn(&mut TrackerList),
+    {
+        self.update_torrents(
+            ids,
+            TorrentGetField::Trackerlist,
+            |torrent| torrent.tracker_list.clone().unwrap_or_default(),
+            update,
+            |tracker_list| TorrentSetArgs {
+                tracker_list: Some(tracker_list),
+                ..TorrentSetArgs::default()
+            },
+        ).await
+    }
+
+    /// Reads a property of the torrents with a torrent get call of `field`,
+    /// changes it with `update` and sets it back with `set`.
+    /// Torrents ending up with the same value are changed with a single torrent set call,
+    /// torrents whose value is unchanged are skipped.
+    async fn update_torrents<T, G, U, S>(
+        &mut self,
+        ids: Option<Vec<Id>>,
+        field: TorrentGetField,
+        get: G,
+        update: U,
+        set: S,
+    ) -> Result<RpcResponse<Nothing>>
+        where
+            T: Clone + PartialEq,
+            G: Fn(&Torrent) -> T,
+            U: Fn(&mut T),
+            S: Fn(T) -> TorrentSetArgs,
+    {
+        let torrents = self
+            .torrent_get(Some(vec![TorrentGetField::Id, field]), ids)
+            .await?;
+        if !torrents.is_ok() {
+            return Ok(RpcResponse { arguments: Nothing {}, result: torrents.result });
+        }
+        let mut changes: Vec<(T, Vec<Id>)> = vec![];
+        for torrent in &torrents.arguments.torrents {
+            let id = match torrent.id {
+                Some(id) => id,
+                None => continue,
+            };
+            let current = get(torrent);
+            let mut value = current.clone();
+            update(&mut value);
+            if value == current {
+                continue;
+            }
+            match changes.iter_mut().find(|(it, _)| *it == value) {
+                Some((_, ids)) => ids.push(Id::Id(id)),
+                None => changes.push((value, vec![Id::Id(id)])),
+            }
+        }
+        let mut response = RpcResponse { arguments: Nothing {}, result: String::from("success") };
+        for (value, ids) in changes {
+            response = self.torrent_set(set(value), Some(ids)).await?;
+            if !response.is_ok() {
+                break;
+            }
+        }
+        Ok(response)
+    }
+
     /// Performs a torrent add call
     ///
     /// # Errors
@@ -548,57 +1106,187 @@ impl TransClient {
     /// }
     /// ```
     pub async fn torrent_add(&mut self, add: TorrentAddArgs) -> Result<RpcResponse<TorrentAdded>> {
-        if add.metainfo == None && add.filename == None {
+        if add.metainfo.is_none() && add.filename.is_none() {
             panic!("Metainfo or Filename should be provided")
         }
         self.call(RpcRequest::torrent_add(add)).await
     }
 
+    /// Performs a batch of JRPC calls of the same response type,
+    /// running at most `concurrency` of them at the same time.
+    /// All calls share the session id of this client.
+    ///
+    /// Results are returned in the order of `requests`, and a failed
+    /// call does not abort the rest of the batch.
+    ///
+    /// # Errors
+    ///
+    /// Never fails as a whole. Each request gets its own result, holding any IO Error or
+    /// Deserialization error of its call, or `TransError::Unsupported` if the daemon's
+    /// rpc-version is too old for it. The other requests are still sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate transmission_rpc;
+    ///
+    /// use std::env;
+    /// use dotenv::dotenv;
+    /// use transmission_rpc::TransClient;
+    /// use transmission_rpc::types::{Result, RpcRequest, RpcResponse, BasicAuth};
+    /// use transmission_rpc::types::{Nothing, Id};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     dotenv().ok();
+    ///     env_logger::init();
+    ///     let url= env::var("TURL")?;
+    ///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+    ///     let mut client = TransClient::with_auth(&url, basic_auth);
+    ///     let requests: Vec<RpcRequest> = (1..=3)
+    ///         .map(|id| RpcRequest::torrent_set_location(vec![Id::Id(id)], format!("/new/location/{}", id), Option::from(false)))
+    ///         .collect();
+    ///     let res: Vec<Result<RpcResponse<Nothing>>> = client.batch(requests, 2).await;
+    ///     for (i, r) in res.iter().enumerate() {
+    ///         println!("Set-location #{} result: {:?}", i, r.as_ref().map(|it| it.is_ok()));
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn batch<RS>(
+        &mut self,
+        requests: Vec<RpcRequest>,
+        concurrency: usize,
+    ) -> Vec<Result<RpcResponse<RS>>>
+        where
+            RS: RpcResponseArgument + DeserializeOwned + std::fmt::Debug,
+    {
+        let this: &TransClient = self;
+        stream::iter(requests)
+            .map(|request| this.call(request))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Performs one JRPC call per chunk of `ids` and merges the responses.
+    /// Stops at the first chunk that is not successful and returns its response,
+    /// the chunks before it are applied and not rolled back.
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error
+    async fn call_chunked<RS, RQ, M>(&self, ids: Vec<Id>, request: RQ, merge: M) -> Result<RpcResponse<RS>>
+        where
+            RS: RpcResponseArgument + DeserializeOwned + std::fmt::Debug,
+            RQ: Fn(Vec<Id>) -> RpcRequest,
+            M: Fn(&mut RS, RS),
+    {
+        let mut merged: Option<RpcResponse<RS>> = None;
+        for chunk in id_chunks(ids, self.id_chunk_size) {
+            let response: RpcResponse<RS> = self.call(request(chunk)).await?;
+            if !response.is_ok() {
+                return Ok(response);
+            }
+            match merged.as_mut() {
+                None => merged = Some(response),
+                Some(it) => merge(&mut it.arguments, response.arguments),
+            }
+        }
+        Ok(merged.expect("At least one chunk is always requested"))
+    }
+
+    /// Performs a session get call and caches the rpc-version of the daemon
+    async fn fetch_session(&self) -> Result<RpcResponse<SessionGet>> {
+        let response: RpcResponse<SessionGet> = self.send(RpcRequest::session_get()).await?;
+        if response.is_ok() {
+            *self.rpc_version.lock().unwrap() = Some(response.arguments.rpc_version);
+        }
+        Ok(response)
+    }
+
+    async fn negotiated_rpc_version(&self) -> Result<i32> {
+        let cached = *self.rpc_version.lock().unwrap();
+        match cached {
+            Some(version) => Ok(version),
+            None => Ok(self.fetch_session().await?.arguments.rpc_version),
+        }
+    }
+
+    /// Performs a JRPC call to the server,
+    /// after checking that the daemon supports it
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error,
+    /// or `TransError::Unsupported` if the daemon's rpc-version is too old for the request
+    async fn call<RS>(&self, request: RpcRequest) -> Result<RpcResponse<RS>>
+        where
+            RS: RpcResponseArgument + DeserializeOwned + std::fmt::Debug,
+    {
+        let required = request.min_rpc_version();
+        if required > 1 {
+            check_rpc_version(&request, required, self.negotiated_rpc_version().await?)?;
+        }
+        self.send(request).await
+    }
+
     /// Performs a JRPC call to the server
     ///
     /// # Errors
     ///
     /// Any IO Error or Deserialization error
-    async fn call<RS>(&mut self, request: RpcRequest) -> Result<RpcResponse<RS>>
+    async fn send<RS>(&self, request: RpcRequest) -> Result<RpcResponse<RS>>
         where
             RS: RpcResponseArgument + DeserializeOwned + std::fmt::Debug,
     {
         let mut remaining_retries = MAX_RETRIES;
+        let mut attempt = 0;
         loop {
-            if remaining_retries <= 0 {
-                return Err(From::from(TransError::MaxRetriesReached));
-            }
-            remaining_retries -= 1;
-
             info!("Loaded auth: {:?}", &self.auth);
-            let rq = match &self.session_id {
-                None => self.rpc_request(),
-                Some(id) => {
-                    self.rpc_request().header("X-Transmission-Session-Id", id)
-                }
-            }.json(&request);
+            let session_id = self.session_id.lock().unwrap().clone();
+            let body = serde_json::to_vec(&request)?;
+            info!("Request body: {:?}", String::from_utf8_lossy(&body));
 
-            info!(
-                "Request body: {:?}",
-                rq.try_clone()
-                    .expect("Unable to get the request body")
-                    .body_string()?
-            );
-
-            let rsp: reqwest::Response = rq.send().await?;
-            match rsp.status() {
+            let rsp = match self.transport.post(self.auth.as_ref(), session_id.as_deref(), body).await {
+                Ok(rsp) => rsp,
+                Err(e) => {
+                    if !self.retry_policy.should_retry(attempt, request.is_idempotent(), &Failure::Transport(e.as_ref())) {
+                        return Err(e);
+                    }
+                    self.backoff(attempt, &e.to_string()).await;
+                    attempt += 1;
+                    continue;
+                }
+            };
+            match rsp.status {
                 StatusCode::CONFLICT => {
-                    let session_id = rsp.headers()
-                        .get("X-Transmission-Session-Id")
-                        .ok_or(TransError::NoSessionIdReceived)?
-                        .to_str()?;
-                    self.session_id = Some(String::from(session_id));
+                    remaining_retries -= 1;
+                    if remaining_retries == 0 {
+                        return Err(From::from(TransError::MaxRetriesReached));
+                    }
+                    let session_id = rsp.session_id
+                        .ok_or(TransError::NoSessionIdReceived)?;
+                    let previous = self.session_id.lock().unwrap().replace(session_id.clone());
+                    if previous.as_ref() != Some(&session_id) {
+                        // a new session may be a restarted, possibly upgraded, daemon
+                        *self.rpc_version.lock().unwrap() = None;
+                    }
 
                     info!("Got new session_id: {}. Retrying request.", session_id);
                     continue;
                 }
+                status if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+                    if !self.retry_policy.should_retry(attempt, request.is_idempotent(), &Failure::Status(status)) {
+                        return Err(From::from(TransError::ServerError(status)));
+                    }
+                    self.backoff(attempt, &status.to_string()).await;
+                    attempt += 1;
+                    continue;
+                }
                 _ => {
-                    let rpc_response: RpcResponse<RS> = rsp.json().await?;
+                    let rpc_response: RpcResponse<RS> = serde_json::from_slice(&rsp.body)?;
                     info!("Response body: {:#?}", rpc_response);
 
                     return Ok(rpc_response)
@@ -606,26 +1294,194 @@ impl TransClient {
             }
         }
     }
+
+    /// Waits before the retry number `attempt` of a failed call
+    async fn backoff(&self, attempt: usize, reason: &str) {
+        let backoff = self.retry_policy.backoff(attempt);
+        warn!("Call failed: {}. Retrying in {:?}.", reason, backoff);
+        tokio::time::sleep(backoff).await;
+    }
 }
 
-trait BodyString {
-    fn body_string(self) -> Result<String>;
+fn check_rpc_version(request: &RpcRequest, required: i32, available: i32) -> Result<()> {
+    if available < required {
+        return Err(From::from(TransError::Unsupported {
+            method: request.method().to_string(),
+            required,
+            available,
+        }));
+    }
+    Ok(())
 }
 
-impl BodyString for reqwest::RequestBuilder {
-    fn body_string(self) -> Result<String> {
-        let rq = self.build()?;
-        let body = rq.body().unwrap().as_bytes().unwrap();
-        Ok(std::str::from_utf8(body)?.to_string())
+/// Drops the fields unknown to a daemon with `rpc_version`
+fn supported_fields(fields: Option<Vec<TorrentGetField>>, rpc_version: i32) -> Result<Vec<TorrentGetField>> {
+    let fields = match fields {
+        None => return Ok(TorrentGetField::all_for(rpc_version)),
+        Some(fields) => fields,
+    };
+    let required = fields.iter().map(TorrentGetField::min_rpc_version).min();
+    let supported: Vec<TorrentGetField> = fields
+        .into_iter()
+        .filter(|field| {
+            let supported = field.is_supported(rpc_version);
+            if !supported {
+                warn!("Field {} is not supported by rpc-version {}", field.to_str(), rpc_version);
+            }
+            supported
+        })
+        .collect();
+    match required {
+        // none of the fields asked for is known to the daemon
+        Some(required) if supported.is_empty() => Err(From::from(TransError::Unsupported {
+            method: "torrent-get".to_string(),
+            required,
+            available: rpc_version,
+        })),
+        _ => Ok(supported),
     }
 }
 
+/// Splits `ids` into chunks of at most `chunk_size` elements.
+/// Always returns at least one chunk, so an empty list is still sent as is.
+fn id_chunks(ids: Vec<Id>, chunk_size: usize) -> Vec<Vec<Id>> {
+    if ids.len() <= chunk_size {
+        return vec![ids];
+    }
+    ids.chunks(chunk_size).map(|chunk| chunk.to_vec()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use dotenv::dotenv;
     use std::env;
 
+    #[test]
+    pub fn test_id_chunks() {
+        let ids: Vec<Id> = (1..=5).map(Id::Id).collect();
+        let chunks = id_chunks(ids.clone(), 2);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].len(), 1);
+        assert_eq!(id_chunks(ids, 5).len(), 1);
+        assert_eq!(id_chunks(vec![], 2), vec![Vec::<Id>::new()]);
+    }
+
+    #[test]
+    pub fn test_check_rpc_version() {
+        let request = RpcRequest::torrent_rename_path(vec![Id::Id(1)], "a".to_string(), "b".to_string());
+        assert_eq!(request.min_rpc_version(), 15);
+        assert!(check_rpc_version(&request, 15, 15).is_ok());
+        let error = check_rpc_version(&request, 15, 14).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransError>(),
+            Some(TransError::Unsupported { required: 15, available: 14, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_supported_fields() {
+        let all = supported_fields(None, 17).unwrap();
+        assert_eq!(all.len(), TorrentGetField::all().len());
+        let old = supported_fields(None, 15).unwrap();
+        assert!(!old.contains(&TorrentGetField::Labels));
+        assert!(!old.contains(&TorrentGetField::Filecount));
+        assert!(old.contains(&TorrentGetField::Etaidle));
+        let requested = supported_fields(Some(vec![TorrentGetField::Id, TorrentGetField::Group]), 16).unwrap();
+        assert_eq!(requested, vec![TorrentGetField::Id]);
+        let error = supported_fields(Some(vec![TorrentGetField::Labels, TorrentGetField::Group]), 15).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<TransError>(),
+            Some(TransError::Unsupported { required: 16, available: 15, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_torrent_field_names() {
+        let torrent: Torrent = serde_json::from_str(
+            r#"{"file-count": 2, "peer-limit": 50, "primary-mime-type": "video/mp4"}"#,
+        ).unwrap();
+        assert_eq!(TorrentGetField::Filecount.to_str(), "file-count");
+        assert_eq!(TorrentGetField::Peerlimit.to_str(), "peer-limit");
+        assert_eq!(TorrentGetField::Primarymimetype.to_str(), "primary-mime-type");
+        assert_eq!(torrent.file_count, Some(2));
+        assert_eq!(torrent.peer_limit, Some(50));
+        assert_eq!(torrent.primary_mime_type.as_deref(), Some("video/mp4"));
+    }
+
+    #[test]
+    pub fn test_torrent_set_request() {
+        let args = TorrentSetArgs {
+            labels: Some(vec![String::from("tv")]),
+            tracker_replace: Some(vec![(2, String::from("https://b/announce"))]),
+            ..TorrentSetArgs::default()
+        };
+        let request = RpcRequest::torrent_set(args, Some(vec![Id::Id(1)]));
+        assert_eq!(request.min_rpc_version(), 16);
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"method":"torrent-set","arguments":{"ids":[1],"labels":["tv"],"trackerReplace":[2,"https://b/announce"]}}"#
+        );
+    }
+
+    #[test]
+    pub fn test_session_set_request() {
+        let args = SessionSetArgs {
+            alt_speed_enabled: Some(true),
+            speed_limit_down: Some(500),
+            ..SessionSetArgs::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&RpcRequest::session_set(args)).unwrap(),
+            r#"{"method":"session-set","arguments":{"alt-speed-enabled":true,"speed-limit-down":500}}"#
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_new_session_clears_rpc_version() -> Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let session = |version: i32| {
+            format!(
+                r#"{{"result":"success","arguments":{{"alt-speed-down":0,"alt-speed-enabled":false,"alt-speed-up":0,
+                "blocklist-enabled":false,"download-dir":"/data","encryption":"preferred",
+                "rpc-version-minimum":1,"rpc-version":{},"version":"4.0.0"}}}}"#,
+                version
+            )
+        };
+        let responses = vec![
+            ("409 Conflict", "X-Transmission-Session-Id: one\r\n".to_string(), String::new()),
+            ("200 OK", String::new(), session(15)),
+            ("409 Conflict", "X-Transmission-Session-Id: two\r\n".to_string(), String::new()),
+            ("200 OK", String::new(), r#"{"result":"success","arguments":{}}"#.to_string()),
+            ("200 OK", String::new(), session(17)),
+        ];
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/transmission/rpc", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            for (status, headers, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\n{}Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let mut client = TransClient::new(&url);
+        assert_eq!(client.rpc_version().await?, 15);
+        client.torrent_action(TorrentAction::Start, vec![Id::Id(1)]).await?;
+        assert_eq!(client.rpc_version().await?, 17);
+        server.await?;
+        Ok(())
+    }
+
     #[tokio::test]
     pub async fn test_malformed_url() -> Result<()> {
         dotenv().ok();
@@ -0,0 +1,662 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::filter::unix_now;
+use crate::retry::Failure;
+use crate::state;
+use crate::types::{Result, Torrent, TorrentGetField, TorrentStatus};
+use crate::{RetryPolicy, TransClient};
+
+/// What happened to a torrent between two polls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Added,
+    /// all wanted data is downloaded
+    Completed,
+    /// the torrent got an error, or a different one
+    Failed,
+    /// the error of the torrent went away
+    Recovered,
+    /// a download has no peer to download from
+    Stalled,
+    Removed,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Added => "added",
+            EventKind::Completed => "completed",
+            EventKind::Failed => "failed",
+            EventKind::Recovered => "recovered",
+            EventKind::Stalled => "stalled",
+            EventKind::Removed => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TorrentEvent {
+    pub event: EventKind,
+    pub id: Option<i64>,
+    pub hash: String,
+    pub name: String,
+    pub download_dir: String,
+    /// the error string, for `Failed` events
+    pub error: Option<String>,
+    pub percent_done: f32,
+    /// unix timestamp of the poll which saw the event
+    pub time: i64,
+}
+
+impl TorrentEvent {
+    /// Identifies the event for deduplication: a torrent completes once,
+    /// but fails once per distinct error
+    fn key(&self) -> String {
+        match self.event {
+            EventKind::Failed => format!("{}:failed:{}", self.hash, self.error.as_deref().unwrap_or("")),
+            _ => format!("{}:{}", self.hash, self.event.as_str()),
+        }
+    }
+
+    fn values(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("event", self.event.as_str().to_string()),
+            ("id", self.id.map(|it| it.to_string()).unwrap_or_default()),
+            ("hash", self.hash.clone()),
+            ("name", self.name.clone()),
+            ("download_dir", self.download_dir.clone()),
+            ("error", self.error.clone().unwrap_or_default()),
+            ("percent_done", self.percent_done.to_string()),
+            ("time", self.time.to_string()),
+        ]
+    }
+
+    /// Replaces `{{event}}`, `{{id}}`, `{{hash}}`, `{{name}}`, `{{download_dir}}`, `{{error}}`,
+    /// `{{percent_done}}` and `{{time}}` in `template`, escaping the values for a JSON string
+    pub fn render_json(&self, template: &str) -> String {
+        self.render(template, |value| {
+            let quoted = serde_json::to_string(value).unwrap_or_default();
+            quoted[1..quoted.len() - 1].to_string()
+        })
+    }
+
+    fn render(&self, template: &str, escape: impl Fn(&str) -> String) -> String {
+        let mut text = template.to_string();
+        for (key, value) in self.values() {
+            text = text.replace(&format!("{{{{{}}}}}", key), &escape(&value));
+        }
+        text
+    }
+}
+
+/// Where events are sent
+#[derive(Debug, Clone)]
+pub enum Sink {
+    /// POSTs the event as JSON, or the rendered `template` if given,
+    /// e.g. `{"text": "{{name}} {{event}}"}`
+    Webhook {
+        url: String,
+        template: Option<String>,
+        headers: Vec<(String, String)>,
+        /// events to send, all of them if empty
+        events: Vec<EventKind>,
+    },
+    /// Runs a program with templated arguments and the event in `TR_EVENT`, `TR_TORRENT_ID`,
+    /// `TR_TORRENT_HASH`, `TR_TORRENT_NAME`, `TR_TORRENT_DIR` and `TR_TORRENT_ERROR`.
+    /// The program fails the delivery if it exits with an error or runs longer than `timeout`.
+    Command {
+        program: String,
+        args: Vec<String>,
+        timeout: Duration,
+        /// events to send, all of them if empty
+        events: Vec<EventKind>,
+    },
+}
+
+impl Sink {
+    pub fn webhook(url: &str) -> Sink {
+        Sink::Webhook {
+            url: url.to_string(),
+            template: None,
+            headers: vec![],
+            events: vec![],
+        }
+    }
+
+    pub fn command(program: &str, args: Vec<String>) -> Sink {
+        Sink::Command {
+            program: program.to_string(),
+            args,
+            timeout: Duration::from_secs(60),
+            events: vec![],
+        }
+    }
+
+    fn wants(&self, event: EventKind) -> bool {
+        let events = match self {
+            Sink::Webhook { events, .. } | Sink::Command { events, .. } => events,
+        };
+        events.is_empty() || events.contains(&event)
+    }
+}
+
+/// How sending an event to the sinks went
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    Sent,
+    /// a sink failed, the event is sent again on the next poll
+    Failed,
+    /// a sink refused the event, e.g. a webhook answering 400, so sending it again is pointless
+    Rejected,
+}
+
+/// A webhook answering with a client error other than 429, which retrying does not fix
+#[derive(Debug)]
+struct Rejected(String);
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for Rejected {}
+
+/// Undelivered events older than this, in seconds, are dropped
+const PENDING_MAX_AGE: i64 = 24 * 3600;
+/// At most this many undelivered events are kept, the oldest ones are dropped first
+const PENDING_MAX_LEN: usize = 1000;
+
+/// Last seen state of a torrent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TorrentState {
+    id: Option<i64>,
+    name: String,
+    download_dir: String,
+    status: Option<i64>,
+    percent_done: f32,
+    error: Option<String>,
+    stalled: bool,
+}
+
+impl TorrentState {
+    fn from(torrent: &Torrent) -> TorrentState {
+        TorrentState {
+            id: torrent.id,
+            name: torrent.name.clone().unwrap_or_default(),
+            download_dir: torrent.download_dir.clone().unwrap_or_default(),
+            status: torrent.status,
+            percent_done: torrent.percent_done.unwrap_or(0.0),
+            error: torrent.error_string.clone().filter(|it| !it.is_empty()),
+            stalled: torrent.is_stalled == Some(true)
+                && torrent.torrent_status() == Some(TorrentStatus::Downloading),
+        }
+    }
+
+    fn event(&self, event: EventKind, hash: &str, time: i64) -> TorrentEvent {
+        TorrentEvent {
+            event,
+            id: self.id,
+            hash: hash.to_string(),
+            name: self.name.clone(),
+            download_dir: self.download_dir.clone(),
+            error: self.error.clone(),
+            percent_done: self.percent_done,
+            time,
+        }
+    }
+}
+
+/// What the notifier remembers between polls and restarts
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct NotifierState {
+    /// false until the first poll, which only records the torrents
+    initialized: bool,
+    torrents: HashMap<String, TorrentState>,
+    /// keys of the events already detected, so they are not sent twice
+    seen: HashSet<String>,
+    /// events whose delivery failed, retried on the next poll, oldest first
+    pending: Vec<TorrentEvent>,
+}
+
+/// Watches torrents for state transitions and sends an event for each one to its sinks.
+///
+/// With a state file, the torrents seen, the events sent and the failed deliveries survive
+/// restarts: transitions happening while the notifier is down are seen on its next poll.
+///
+/// ```
+/// use transmission_rpc::notifier::{EventKind, Notifier, Sink};
+///
+/// let sinks = vec![
+///     Sink::Webhook {
+///         url: "https://chat.example.com/hooks/abc".to_string(),
+///         template: Some(r#"{"text": "{{name}} {{event}} {{error}}"}"#.to_string()),
+///         headers: vec![],
+///         events: vec![EventKind::Completed, EventKind::Failed],
+///     },
+///     Sink::command("/usr/local/bin/on-torrent-event", vec!["{{hash}}".to_string()]),
+/// ];
+/// let notifier = Notifier::new(sinks);
+/// ```
+#[derive(Debug)]
+pub struct Notifier {
+    sinks: Vec<Sink>,
+    state_path: Option<PathBuf>,
+    state: NotifierState,
+    http: reqwest::Client,
+    retry_policy: RetryPolicy,
+}
+
+impl Notifier {
+    pub fn new(sinks: Vec<Sink>) -> Notifier {
+        Notifier {
+            sinks,
+            state_path: None,
+            state: NotifierState::default(),
+            http: reqwest::Client::new(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Returns a notifier keeping its state in `path`, loading it if the file exists
+    ///
+    /// # Errors
+    ///
+    /// IO Error or Deserialization error of an existing state file
+    pub fn with_state_file(sinks: Vec<Sink>, path: PathBuf) -> Result<Notifier> {
+        let state = state::load(&path)?;
+        Ok(Notifier {
+            state_path: Some(path),
+            state,
+            ..Notifier::new(sinks)
+        })
+    }
+
+    /// Sets how failed webhook deliveries are retried within a poll
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Events whose delivery failed and will be retried on the next poll.
+    /// Events undelivered for a day, refused by a sink, or beyond the 1000 most recent are dropped.
+    pub fn pending(&self) -> &[TorrentEvent] {
+        &self.state.pending
+    }
+
+    /// Fields to request with torrent-get
+    pub fn fields() -> Vec<TorrentGetField> {
+        vec![
+            TorrentGetField::Id,
+            TorrentGetField::HashString,
+            TorrentGetField::Name,
+            TorrentGetField::Downloaddir,
+            TorrentGetField::Status,
+            TorrentGetField::Percentdone,
+            TorrentGetField::Errorstring,
+            TorrentGetField::Isstalled,
+        ]
+    }
+
+    /// Compares `torrents` with the previous poll and returns the new events
+    pub fn detect(&mut self, torrents: &[Torrent]) -> Vec<TorrentEvent> {
+        self.detect_at(torrents, unix_now())
+    }
+
+    fn detect_at(&mut self, torrents: &[Torrent], now: i64) -> Vec<TorrentEvent> {
+        let current: HashMap<String, TorrentState> = torrents
+            .iter()
+            .filter_map(|it| Some((it.hash_string.clone()?.to_lowercase(), TorrentState::from(it))))
+            .collect();
+        let previous = std::mem::replace(&mut self.state.torrents, current);
+        if !self.state.initialized {
+            self.state.initialized = true;
+            return vec![];
+        }
+
+        let mut events = vec![];
+        for (hash, state) in &self.state.torrents {
+            let mut kinds = vec![];
+            match previous.get(hash) {
+                None => {
+                    kinds.push(EventKind::Added);
+                    if state.percent_done >= 1.0 {
+                        kinds.push(EventKind::Completed);
+                    }
+                    if state.error.is_some() {
+                        kinds.push(EventKind::Failed);
+                    }
+                }
+                Some(before) => {
+                    if before.percent_done < 1.0 && state.percent_done >= 1.0 {
+                        kinds.push(EventKind::Completed);
+                    }
+                    if state.error.is_some() && state.error != before.error {
+                        kinds.push(EventKind::Failed);
+                    }
+                    if state.error.is_none() && before.error.is_some() {
+                        kinds.push(EventKind::Recovered);
+                    }
+                    if state.stalled && !before.stalled {
+                        kinds.push(EventKind::Stalled);
+                    }
+                }
+            }
+            // conditions that can come back are sent again once they went away
+            if state.error.is_none() {
+                let prefix = format!("{}:failed:", hash);
+                self.state.seen.retain(|it| !it.starts_with(&prefix));
+            }
+            if !state.stalled {
+                self.state.seen.remove(&format!("{}:stalled", hash));
+            }
+            if state.percent_done < 1.0 {
+                // e.g. more files were wanted, or the data was found missing by a verify
+                self.state.seen.remove(&format!("{}:completed", hash));
+            }
+            self.state.seen.remove(&format!("{}:recovered", hash));
+            events.extend(kinds.into_iter().map(|kind| state.event(kind, hash, now)));
+        }
+        for (hash, state) in &previous {
+            if !self.state.torrents.contains_key(hash) {
+                let prefix = format!("{}:", hash);
+                self.state.seen.retain(|it| !it.starts_with(&prefix));
+                events.push(state.event(EventKind::Removed, hash, now));
+            }
+        }
+        events.retain(|it| self.state.seen.insert(it.key()) || it.event == EventKind::Removed);
+        events.sort_by_key(|it| (it.time, it.hash.clone(), it.event as u8));
+        events
+    }
+
+    /// Fetches the torrents, detects events and sends them along with the pending ones,
+    /// then saves the state. Returns the events sent successfully.
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error of torrent-get or of the state file
+    pub async fn poll(&mut self, client: &mut TransClient) -> Result<Vec<TorrentEvent>> {
+        let response = client.torrent_get(Some(Notifier::fields()), None).await?;
+        if !response.is_ok() {
+            return Err(From::from(format!("torrent-get failed: {}", response.result)));
+        }
+        let mut events = std::mem::take(&mut self.state.pending);
+        events.extend(self.detect(&response.arguments.torrents));
+        let mut sent = vec![];
+        for event in events {
+            match self.send(&event).await {
+                Delivery::Sent => sent.push(event),
+                Delivery::Rejected => {
+                    warn!("Dropping {} event of {}, a sink refused it", event.event.as_str(), event.name)
+                }
+                Delivery::Failed => self.state.pending.push(event),
+            }
+        }
+        self.prune_pending(unix_now());
+        self.save()?;
+        Ok(sent)
+    }
+
+    /// Polls the torrents every `interval` and sends their events
+    ///
+    /// # Errors
+    ///
+    /// The error of the first poll that fails to get the torrents or save the state;
+    /// failed deliveries are kept for the next poll instead
+    pub async fn watch(&mut self, client: &mut TransClient, interval: Duration) -> Result<()> {
+        loop {
+            self.poll(client).await?;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Drops the pending events too old to be worth sending, then the oldest ones beyond the limit
+    fn prune_pending(&mut self, now: i64) {
+        self.state.pending.retain(|event| {
+            let expired = now - event.time > PENDING_MAX_AGE;
+            if expired {
+                warn!("Dropping {} event of {}, undelivered for a day", event.event.as_str(), event.name);
+            }
+            !expired
+        });
+        if self.state.pending.len() > PENDING_MAX_LEN {
+            let dropped = self.state.pending.len() - PENDING_MAX_LEN;
+            warn!("Dropping the {} oldest undelivered events", dropped);
+            self.state.pending.drain(..dropped);
+        }
+    }
+
+    /// Sends the event to all sinks wanting it.
+    /// A failed event is sent again to all its sinks, so sinks should tolerate duplicates;
+    /// it is rejected only if no sink failed otherwise.
+    pub async fn send(&self, event: &TorrentEvent) -> Delivery {
+        let mut delivery = Delivery::Sent;
+        for sink in self.sinks.iter().filter(|it| it.wants(event.event)) {
+            let result = match sink {
+                Sink::Webhook {
+                    url,
+                    template,
+                    headers,
+                    ..
+                } => self.post(url, template.as_deref(), headers, event).await,
+                Sink::Command {
+                    program,
+                    args,
+                    timeout,
+                    ..
+                } => run_command(program, args, *timeout, event).await,
+            };
+            if let Err(e) = result {
+                warn!("Unable to send {} event of {}: {}", event.event.as_str(), event.name, e);
+                delivery = match (delivery, e.is::<Rejected>()) {
+                    (Delivery::Failed, _) | (_, false) => Delivery::Failed,
+                    _ => Delivery::Rejected,
+                };
+            }
+        }
+        delivery
+    }
+
+    async fn post(
+        &self,
+        url: &str,
+        template: Option<&str>,
+        headers: &[(String, String)],
+        event: &TorrentEvent,
+    ) -> Result<()> {
+        let body = match template {
+            Some(template) => event.render_json(template),
+            None => serde_json::to_string(event)?,
+        };
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .http
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            for (name, value) in headers {
+                request = request.header(name.as_str(), value.as_str());
+            }
+            // receivers get an event at least once, so retrying a POST is fine
+            let error: Box<dyn std::error::Error + Send + Sync> = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                        return Err(Box::new(Rejected(format!("{} responded with {}", url, status))));
+                    }
+                    if !self.retry_policy.should_retry(attempt, true, &Failure::Status(status)) {
+                        return Err(From::from(format!("{} responded with {}", url, status)));
+                    }
+                    From::from(format!("{} responded with {}", url, status))
+                }
+                Err(e) => {
+                    if !self.retry_policy.should_retry(attempt, true, &Failure::Transport(&e)) {
+                        return Err(Box::new(e));
+                    }
+                    Box::new(e)
+                }
+            };
+            let backoff = self.retry_policy.backoff(attempt);
+            debug!("Webhook failed: {}. Retrying in {:?}.", error, backoff);
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        state::save(self.state_path.as_deref(), &self.state)
+    }
+}
+
+async fn run_command(program: &str, args: &[String], timeout: Duration, event: &TorrentEvent) -> Result<()> {
+    let args: Vec<String> = args.iter().map(|it| event.render(it, str::to_string)).collect();
+    let mut child = tokio::process::Command::new(program)
+        .args(&args)
+        .env("TR_EVENT", event.event.as_str())
+        .env("TR_TORRENT_ID", event.id.map(|it| it.to_string()).unwrap_or_default())
+        .env("TR_TORRENT_HASH", &event.hash)
+        .env("TR_TORRENT_NAME", &event.name)
+        .env("TR_TORRENT_DIR", &event.download_dir)
+        .env("TR_TORRENT_ERROR", event.error.as_deref().unwrap_or(""))
+        .kill_on_drop(true)
+        .spawn()?;
+    let status = tokio::time::timeout(timeout, child.wait())
+        .await
+        .map_err(|_| format!("{} timed out", program))??;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(From::from(format!("{} exited with {}", program, status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn torrent(hash: &str, status: i64, percent_done: f32, error: &str, stalled: bool) -> Torrent {
+        serde_json::from_value(serde_json::json!({
+            "id": 1, "hashString": hash, "name": "Ubuntu", "downloadDir": "/downloads",
+            "status": status, "percentDone": percent_done, "errorString": error, "isStalled": stalled
+        }))
+        .unwrap()
+    }
+
+    fn kinds(events: &[TorrentEvent]) -> Vec<(&str, EventKind)> {
+        events.iter().map(|it| (it.hash.as_str(), it.event)).collect()
+    }
+
+    #[test]
+    pub fn test_detect_transitions() {
+        let mut notifier = Notifier::new(vec![]);
+        assert!(notifier.detect_at(&[torrent("a", 4, 0.5, "", false)], 0).is_empty());
+
+        let events = notifier.detect_at(&[torrent("a", 4, 0.7, "", true), torrent("b", 4, 0.0, "", false)], 1);
+        assert_eq!(kinds(&events), vec![("a", EventKind::Stalled), ("b", EventKind::Added)]);
+
+        let events = notifier.detect_at(&[torrent("a", 6, 1.0, "", false)], 2);
+        assert_eq!(kinds(&events), vec![("a", EventKind::Completed), ("b", EventKind::Removed)]);
+
+        // verify finding the data complete again does not send a second completion
+        notifier.detect_at(&[torrent("a", 2, 1.0, "", false)], 3);
+        assert!(notifier.detect_at(&[torrent("a", 6, 1.0, "", false)], 4).is_empty());
+
+        let events = notifier.detect_at(&[torrent("a", 6, 1.0, "No data found", false)], 5);
+        assert_eq!(events[0].event, EventKind::Failed);
+        assert_eq!(events[0].error.as_deref(), Some("No data found"));
+        assert!(notifier.detect_at(&[torrent("a", 6, 1.0, "No data found", false)], 6).is_empty());
+        let events = notifier.detect_at(&[torrent("a", 6, 1.0, "", false)], 7);
+        assert_eq!(kinds(&events), vec![("a", EventKind::Recovered)]);
+        let events = notifier.detect_at(&[torrent("a", 6, 1.0, "No data found", false)], 8);
+        assert_eq!(kinds(&events), vec![("a", EventKind::Failed)]);
+    }
+
+    #[test]
+    pub fn test_render_template() {
+        let mut notifier = Notifier::new(vec![]);
+        notifier.detect_at(&[], 0);
+        let mut added = torrent("a", 4, 0.5, "", false);
+        added.name = Some("Say \"hi\"".to_string());
+        let event = &notifier.detect_at(&[added], 42)[0];
+        let body = event.render_json(r#"{"text": "{{name}} was {{event}} at {{time}}"}"#);
+        assert_eq!(body, r#"{"text": "Say \"hi\" was added at 42"}"#);
+        assert!(serde_json::from_str::<serde_json::Value>(&body).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    pub async fn test_command_sink() {
+        let event = TorrentState::from(&torrent("a", 4, 0.5, "", false)).event(EventKind::Added, "a", 0);
+        let check = r#"test "$TR_EVENT" = added && test "$TR_TORRENT_NAME" = Ubuntu && test "$0" = a"#;
+        let notifier = Notifier::new(vec![Sink::command("sh", vec!["-c".to_string(), check.to_string(), "{{hash}}".to_string()])]);
+        assert_eq!(notifier.send(&event).await, Delivery::Sent);
+        let notifier = Notifier::new(vec![Sink::command("sh", vec!["-c".to_string(), "exit 3".to_string()])]);
+        assert_eq!(notifier.send(&event).await, Delivery::Failed);
+    }
+
+    #[tokio::test]
+    pub async fn test_webhook_rejection() -> Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let event = TorrentState::from(&torrent("a", 4, 0.5, "", false)).event(EventKind::Added, "a", 0);
+        let cases = [("400 Bad Request", Delivery::Rejected), ("429 Too Many Requests", Delivery::Failed)];
+        for (status, delivery) in cases {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+            let url = format!("http://{}/hook", listener.local_addr()?);
+            let server = tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer).await;
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            });
+            let mut notifier = Notifier::new(vec![Sink::webhook(&url)]);
+            notifier.set_retry_policy(RetryPolicy::none());
+            assert_eq!(notifier.send(&event).await, delivery);
+            server.await?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_prune_pending() {
+        let mut notifier = Notifier::new(vec![]);
+        let state = TorrentState::from(&torrent("a", 4, 0.5, "", false));
+        notifier.state.pending.push(state.event(EventKind::Stalled, "a", 0));
+        for time in 0..PENDING_MAX_LEN as i64 + 10 {
+            notifier.state.pending.push(state.event(EventKind::Added, "a", PENDING_MAX_AGE + time));
+        }
+        notifier.prune_pending(PENDING_MAX_AGE + 100);
+        assert_eq!(notifier.pending().len(), PENDING_MAX_LEN);
+        assert_eq!(notifier.pending()[0].time, PENDING_MAX_AGE + 10);
+    }
+
+    #[test]
+    pub fn test_state_survives_restart() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("notifier-test-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("state.json");
+        let _ = fs::remove_file(&path);
+
+        let mut notifier = Notifier::with_state_file(vec![], path.clone())?;
+        notifier.detect_at(&[torrent("a", 4, 0.5, "", false)], 0);
+        notifier.state.pending.push(notifier.state.torrents["a"].event(EventKind::Stalled, "a", 0));
+        notifier.save()?;
+
+        let mut restarted = Notifier::with_state_file(vec![], path.clone())?;
+        assert_eq!(restarted.pending().len(), 1);
+        let events = restarted.detect_at(&[torrent("a", 6, 1.0, "", false)], 1);
+        assert_eq!(kinds(&events), vec![("a", EventKind::Completed)]);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use crate::filter::{unix_now, TorrentFilter};
+use crate::types::{Id, Result, Torrent, TorrentGetField};
+use crate::TransClient;
+
+/// Decides when the torrents it applies to are removed
+#[derive(Debug, Clone)]
+pub struct RetentionRule {
+    pub name: String,
+    /// torrents this rule is responsible for
+    pub applies_to: TorrentFilter,
+    /// torrents matching `applies_to` are removed when they also match this
+    pub remove_when: TorrentFilter,
+    /// remove the downloaded data together with the torrent
+    pub delete_local_data: bool,
+}
+
+/// An ordered list of retention rules.
+/// Each torrent is handled by the first rule whose `applies_to` matches it,
+/// so more specific rules go first.
+///
+/// ```
+/// use std::time::Duration;
+/// use transmission_rpc::filter::TorrentFilter;
+/// use transmission_rpc::policy::{RetentionPolicy, RetentionRule};
+///
+/// const DAY: u64 = 24 * 3600;
+/// let policy = RetentionPolicy {
+///     rules: vec![
+///         // keep private-tracker torrents seeding for 90 days
+///         RetentionRule {
+///             name: "private".to_string(),
+///             applies_to: TorrentFilter::IsPrivate,
+///             remove_when: TorrentFilter::SeededAtLeast(Duration::from_secs(90 * DAY)),
+///             delete_local_data: true,
+///         },
+///         // remove the others at ratio 2 or after 7 days of seeding
+///         RetentionRule {
+///             name: "public".to_string(),
+///             applies_to: TorrentFilter::All(vec![]),
+///             remove_when: TorrentFilter::RatioAtLeast(2.0)
+///                 .or(TorrentFilter::SeededAtLeast(Duration::from_secs(7 * DAY))),
+///             delete_local_data: true,
+///         },
+///     ],
+///     dry_run: true,
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    pub rules: Vec<RetentionRule>,
+    /// only report what would be removed
+    pub dry_run: bool,
+}
+
+/// A torrent removed, or to be removed in a dry run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Removal {
+    /// name of the rule removing the torrent
+    pub rule: String,
+    pub id: i64,
+    pub hash: Option<String>,
+    pub name: Option<String>,
+    pub delete_local_data: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub removals: Vec<Removal>,
+}
+
+impl RetentionPolicy {
+    /// Fields to request with torrent-get to evaluate the policy
+    pub fn fields(&self) -> Vec<TorrentGetField> {
+        let mut fields = vec![TorrentGetField::Id, TorrentGetField::Name, TorrentGetField::HashString];
+        for rule in &self.rules {
+            for field in rule.applies_to.fields().into_iter().chain(rule.remove_when.fields()) {
+                if !fields.contains(&field) {
+                    fields.push(field);
+                }
+            }
+        }
+        fields
+    }
+
+    /// Returns the torrents to remove
+    pub fn evaluate(&self, torrents: &[Torrent]) -> Vec<Removal> {
+        self.evaluate_at(torrents, unix_now())
+    }
+
+    /// Same as `evaluate`, with durations counted back from the unix timestamp `now`
+    pub fn evaluate_at(&self, torrents: &[Torrent], now: i64) -> Vec<Removal> {
+        torrents
+            .iter()
+            .filter_map(|torrent| {
+                let id = torrent.id?;
+                let rule = self.rules.iter().find(|it| it.applies_to.matches_at(torrent, now))?;
+                if !rule.remove_when.matches_at(torrent, now) {
+                    return None;
+                }
+                Some(Removal {
+                    rule: rule.name.clone(),
+                    id,
+                    hash: torrent.hash_string.clone(),
+                    name: torrent.name.clone(),
+                    delete_local_data: rule.delete_local_data,
+                })
+            })
+            .collect()
+    }
+
+    /// Fetches all torrents, evaluates the policy and removes the torrents, unless in dry run
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error or Deserialization error, or an error if the daemon refuses the removal
+    pub async fn run(&self, client: &mut TransClient) -> Result<RetentionReport> {
+        let response = client.torrent_get(Some(self.fields()), None).await?;
+        if !response.is_ok() {
+            return Err(From::from(format!("torrent-get failed: {}", response.result)));
+        }
+        let removals = self.evaluate(&response.arguments.torrents);
+        for removal in &removals {
+            info!(
+                "{}Rule {} removes torrent {} ({})",
+                if self.dry_run { "[dry run] " } else { "" },
+                removal.rule,
+                removal.id,
+                removal.name.as_deref().unwrap_or("")
+            );
+        }
+        if !self.dry_run {
+            for delete_local_data in &[false, true] {
+                let ids: Vec<Id> = removals
+                    .iter()
+                    .filter(|it| it.delete_local_data == *delete_local_data)
+                    .map(|it| match &it.hash {
+                        Some(hash) => Id::Hash(hash.clone()),
+                        None => Id::Id(it.id),
+                    })
+                    .collect();
+                if ids.is_empty() {
+                    continue;
+                }
+                let response = client.torrent_remove(ids, *delete_local_data).await?;
+                if !response.is_ok() {
+                    return Err(From::from(format!("torrent-remove failed: {}", response.result)));
+                }
+            }
+        }
+        Ok(RetentionReport {
+            dry_run: self.dry_run,
+            removals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const DAY: u64 = 24 * 3600;
+
+    #[test]
+    pub fn test_first_matching_rule_decides() {
+        let torrents: Vec<Torrent> = serde_json::from_str(
+            r#"[
+                {"id": 1, "isPrivate": true, "uploadRatio": 5.0, "secondsSeeding": 864000},
+                {"id": 2, "isPrivate": true, "uploadRatio": 1.0, "secondsSeeding": 8640000},
+                {"id": 3, "isPrivate": false, "uploadRatio": 2.5, "secondsSeeding": 3600},
+                {"id": 4, "isPrivate": false, "uploadRatio": 0.1, "secondsSeeding": 3600},
+                {"id": 5, "isPrivate": false, "uploadRatio": 2.0, "secondsSeeding": 3600},
+                {"id": 6, "isPrivate": false, "uploadRatio": 0.1, "secondsSeeding": 604800}
+            ]"#,
+        ).unwrap();
+        let policy = RetentionPolicy {
+            rules: vec![
+                RetentionRule {
+                    name: "private".to_string(),
+                    applies_to: TorrentFilter::IsPrivate,
+                    remove_when: TorrentFilter::SeededAtLeast(Duration::from_secs(90 * DAY)),
+                    delete_local_data: false,
+                },
+                RetentionRule {
+                    name: "public".to_string(),
+                    applies_to: TorrentFilter::All(vec![]),
+                    remove_when: TorrentFilter::RatioAtLeast(2.0)
+                        .or(TorrentFilter::SeededAtLeast(Duration::from_secs(7 * DAY))),
+                    delete_local_data: true,
+                },
+            ],
+            dry_run: true,
+        };
+        let removals = policy.evaluate(&torrents);
+        let removed: Vec<(i64, &str, bool)> = removals
+            .iter()
+            .map(|it| (it.id, it.rule.as_str(), it.delete_local_data))
+            .collect();
+        assert_eq!(removed, vec![(2, "private", false), (3, "public", true), (5, "public", true), (6, "public", true)]);
+        assert!(policy.fields().contains(&TorrentGetField::Isprivate));
+        assert!(policy.fields().contains(&TorrentGetField::Secondsseeding));
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::filter::is_below;
+use crate::types::{Id, Result, Torrent, TorrentAction, TorrentGetField, TorrentStatus};
+use crate::TransClient;
+
+/// A torrent to move
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub id: Id,
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Where a relocation stands
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelocationState {
+    Queued,
+    /// set-location was sent, the daemon is moving the data
+    Moving,
+    Verifying,
+    Done,
+    /// the reason it failed
+    Failed(String),
+}
+
+/// Counts of the relocations by state, given to the progress callback after each poll
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    pub total: usize,
+    pub queued: usize,
+    pub moving: usize,
+    pub verifying: usize,
+    pub done: usize,
+    pub failed: usize,
+}
+
+/// Moves the data of torrents to new download dirs and waits until the daemon is done.
+///
+/// set-location returns before the data is moved, and the daemon reports the new download dir
+/// only once it is, so a move is done when the torrent shows the new dir. A failed move leaves
+/// the old dir without reporting an error, so moves not done within `timeout` are failed.
+///
+/// ```
+/// use std::time::Duration;
+/// use transmission_rpc::relocate::Relocator;
+///
+/// let relocator = Relocator {
+///     concurrency: 2,
+///     verify: true,
+///     ..Relocator::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct Relocator {
+    /// how many torrents are moved or verified at once
+    pub concurrency: usize,
+    /// verify the torrents once moved
+    pub verify: bool,
+    pub poll_interval: Duration,
+    /// time a single move or verification may take
+    pub timeout: Duration,
+}
+
+impl Default for Relocator {
+    fn default() -> Relocator {
+        Relocator {
+            concurrency: 1,
+            verify: false,
+            poll_interval: Duration::from_secs(2),
+            timeout: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl Relocator {
+    /// Fields `plan` needs
+    pub fn fields() -> Vec<TorrentGetField> {
+        vec![
+            TorrentGetField::Id,
+            TorrentGetField::HashString,
+            TorrentGetField::Name,
+            TorrentGetField::Downloaddir,
+        ]
+    }
+
+    /// Relocations of the torrents whose download dir is `old_prefix` or below it,
+    /// to the same path below `new_prefix`
+    pub fn plan(torrents: &[Torrent], old_prefix: &str, new_prefix: &str) -> Vec<Relocation> {
+        let old_prefix = old_prefix.trim_end_matches('/');
+        let new_prefix = new_prefix.trim_end_matches('/');
+        torrents
+            .iter()
+            .filter_map(|torrent| {
+                let from = torrent.download_dir.as_ref()?.trim_end_matches('/');
+                if !is_below(from, old_prefix) {
+                    return None;
+                }
+                let id = match (&torrent.hash_string, torrent.id) {
+                    (Some(hash), _) => Id::Hash(hash.to_lowercase()),
+                    (None, Some(id)) => Id::Id(id),
+                    _ => return None,
+                };
+                Some(Relocation {
+                    id,
+                    name: torrent.name.clone().unwrap_or_default(),
+                    from: from.to_string(),
+                    to: format!("{}{}", new_prefix, &from[old_prefix.len()..]),
+                })
+            })
+            .collect()
+    }
+
+    /// Moves the torrents, at most `concurrency` at once, calling `on_progress` after each poll
+    /// and once the moves are started.
+    /// Returns the final state of each relocation, in order. If calling the daemon fails, the
+    /// relocations started are failed with the error and the ones not started are left queued.
+    pub async fn run<F: FnMut(&Progress)>(
+        &self,
+        client: &mut TransClient,
+        relocations: Vec<Relocation>,
+        mut on_progress: F,
+    ) -> Vec<(Relocation, RelocationState)> {
+        let mut states: Vec<(Relocation, RelocationState)> = relocations
+            .into_iter()
+            .map(|it| (it, RelocationState::Queued))
+            .collect();
+        let mut queue: VecDeque<usize> = (0..states.len()).collect();
+        // index, start and local error before the move of the relocations moving or verifying
+        let mut active: Vec<(usize, Instant, Option<String>)> = vec![];
+        loop {
+            if let Err(e) = self.start(client, &mut states, &mut queue, &mut active).await {
+                fail(&mut states, &active, &e.to_string());
+                on_progress(&progress(&states));
+                break;
+            }
+            on_progress(&progress(&states));
+            if active.is_empty() {
+                break;
+            }
+            tokio::time::sleep(self.poll_interval).await;
+
+            if let Err(e) = self.poll(client, &mut states, &mut active).await {
+                fail(&mut states, &active, &e.to_string());
+                on_progress(&progress(&states));
+                break;
+            }
+        }
+        states
+    }
+
+    /// Sends set-location for queued relocations until `concurrency` are active
+    async fn start(
+        &self,
+        client: &mut TransClient,
+        states: &mut [(Relocation, RelocationState)],
+        queue: &mut VecDeque<usize>,
+        active: &mut Vec<(usize, Instant, Option<String>)>,
+    ) -> Result<()> {
+        let count = self.concurrency.max(1).saturating_sub(active.len()).min(queue.len());
+        if count == 0 {
+            return Ok(());
+        }
+        let starting: Vec<usize> = queue.drain(..count).collect();
+        // a torrent may already have a local error, only a new one fails its move
+        let ids = starting.iter().map(|i| states[*i].0.id.clone()).collect();
+        let fields = vec![
+            TorrentGetField::Id,
+            TorrentGetField::HashString,
+            TorrentGetField::Error,
+            TorrentGetField::Errorstring,
+        ];
+        let response = client.torrent_get(Some(fields), Some(ids)).await?;
+        if !response.is_ok() {
+            return Err(From::from(format!("torrent-get failed: {}", response.result)));
+        }
+        let torrents = response.arguments.torrents;
+        for i in starting {
+            let (relocation, state) = &mut states[i];
+            let error = torrents
+                .iter()
+                .find(|it| matches_id(it, &relocation.id))
+                .and_then(local_error);
+            let response = match client
+                .torrent_set_location(vec![relocation.id.clone()], relocation.to.clone(), Some(true))
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    // the daemon may have started the move, it is failed with the active ones
+                    active.push((i, Instant::now(), error));
+                    return Err(e);
+                }
+            };
+            if response.is_ok() {
+                *state = RelocationState::Moving;
+                active.push((i, Instant::now(), error));
+            } else {
+                *state = RelocationState::Failed(response.result);
+            }
+        }
+        Ok(())
+    }
+
+    /// Polls the active relocations, starting the verification of the ones moved
+    async fn poll(
+        &self,
+        client: &mut TransClient,
+        states: &mut [(Relocation, RelocationState)],
+        active: &mut Vec<(usize, Instant, Option<String>)>,
+    ) -> Result<()> {
+        let ids = active.iter().map(|(i, _, _)| states[*i].0.id.clone()).collect();
+        let fields = vec![
+            TorrentGetField::Id,
+            TorrentGetField::HashString,
+            TorrentGetField::Downloaddir,
+            TorrentGetField::Status,
+            TorrentGetField::Error,
+            TorrentGetField::Errorstring,
+        ];
+        let response = client.torrent_get(Some(fields), Some(ids)).await?;
+        if !response.is_ok() {
+            return Err(From::from(format!("torrent-get failed: {}", response.result)));
+        }
+        let torrents = response.arguments.torrents;
+        let mut still_active = vec![];
+        let mut polled = std::mem::take(active).into_iter();
+        while let Some((i, started, error)) = polled.next() {
+            let (relocation, state) = &mut states[i];
+            let torrent = torrents.iter().find(|it| matches_id(it, &relocation.id));
+            let next = match torrent {
+                None => RelocationState::Failed("Torrent removed".to_string()),
+                Some(torrent) => self.next_state(state, relocation, torrent, error.as_deref(), started.elapsed()),
+            };
+            if next == RelocationState::Verifying && *state == RelocationState::Moving {
+                let response = match client
+                    .torrent_action(TorrentAction::Verify, vec![relocation.id.clone()])
+                    .await
+                {
+                    Ok(response) => response,
+                    Err(e) => {
+                        // keeps the ones not done active, so that they are failed
+                        still_active.push((i, started, error));
+                        still_active.extend(polled);
+                        *active = still_active;
+                        return Err(e);
+                    }
+                };
+                *state = if response.is_ok() {
+                    still_active.push((i, Instant::now(), error));
+                    next
+                } else {
+                    RelocationState::Failed(response.result)
+                };
+                continue;
+            }
+            if matches!(next, RelocationState::Moving | RelocationState::Verifying) {
+                still_active.push((i, started, error));
+            }
+            *state = next;
+        }
+        *active = still_active;
+        Ok(())
+    }
+
+    /// State of an active relocation, from its torrent's last poll
+    fn next_state(
+        &self,
+        state: &RelocationState,
+        relocation: &Relocation,
+        torrent: &Torrent,
+        error_before: Option<&str>,
+        elapsed: Duration,
+    ) -> RelocationState {
+        if let Some(error) = local_error(torrent) {
+            if error_before != Some(error.as_str()) {
+                return RelocationState::Failed(error);
+            }
+        }
+        let verifying = matches!(
+            torrent.status.and_then(TorrentStatus::from_i64),
+            Some(TorrentStatus::QueuedToVerify) | Some(TorrentStatus::Verifying)
+        );
+        let settled = match state {
+            RelocationState::Moving => {
+                let dir = torrent.download_dir.as_deref().unwrap_or("").trim_end_matches('/');
+                dir == relocation.to && !verifying
+            }
+            _ => !verifying,
+        };
+        if settled {
+            if *state == RelocationState::Moving && self.verify {
+                RelocationState::Verifying
+            } else {
+                RelocationState::Done
+            }
+        } else if elapsed > self.timeout {
+            let what = if *state == RelocationState::Moving { "Move" } else { "Verification" };
+            RelocationState::Failed(format!("{} not done after {}s", what, self.timeout.as_secs()))
+        } else {
+            state.clone()
+        }
+    }
+}
+
+fn matches_id(torrent: &Torrent, id: &Id) -> bool {
+    match id {
+        Id::Hash(hash) => torrent
+            .hash_string
+            .as_ref()
+            .is_some_and(|it| it.eq_ignore_ascii_case(hash)),
+        Id::Id(id) => torrent.id == Some(*id),
+    }
+}
+
+/// The local error of the torrent, 1 and 2 are tracker errors, 3 is a local one
+fn local_error(torrent: &Torrent) -> Option<String> {
+    if torrent.error == Some(3) {
+        Some(torrent.error_string.clone().unwrap_or_default())
+    } else {
+        None
+    }
+}
+
+/// Fails the active relocations, e.g. when the daemon can no longer be called
+fn fail(states: &mut [(Relocation, RelocationState)], active: &[(usize, Instant, Option<String>)], reason: &str) {
+    for (i, _, _) in active {
+        states[*i].1 = RelocationState::Failed(reason.to_string());
+    }
+}
+
+fn progress(states: &[(Relocation, RelocationState)]) -> Progress {
+    let mut progress = Progress {
+        total: states.len(),
+        ..Progress::default()
+    };
+    for (_, state) in states {
+        match state {
+            RelocationState::Queued => progress.queued += 1,
+            RelocationState::Moving => progress.moving += 1,
+            RelocationState::Verifying => progress.verifying += 1,
+            RelocationState::Done => progress.done += 1,
+            RelocationState::Failed(_) => progress.failed += 1,
+        }
+    }
+    progress
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrents() -> Vec<Torrent> {
+        serde_json::from_str(
+            r#"[
+                {"id": 1, "hashString": "AB", "name": "Show", "downloadDir": "/data/tv/"},
+                {"id": 2, "hashString": "CD", "name": "Show 2", "downloadDir": "/data/tv/shows"},
+                {"id": 3, "hashString": "EF", "name": "Movie", "downloadDir": "/data/tvshows"},
+                {"id": 4, "name": "Other", "downloadDir": "/data/movies"}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    pub fn test_plan() {
+        let plan = Relocator::plan(&torrents(), "/data/tv/", "/mnt/big");
+        assert_eq!(
+            plan,
+            vec![
+                Relocation {
+                    id: Id::Hash("ab".to_string()),
+                    name: "Show".to_string(),
+                    from: "/data/tv".to_string(),
+                    to: "/mnt/big".to_string(),
+                },
+                Relocation {
+                    id: Id::Hash("cd".to_string()),
+                    name: "Show 2".to_string(),
+                    from: "/data/tv/shows".to_string(),
+                    to: "/mnt/big/shows".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_next_state() {
+        let relocator = Relocator {
+            verify: true,
+            ..Relocator::default()
+        };
+        let relocation = Relocator::plan(&torrents(), "/data/tv", "/mnt/big").remove(0);
+        let torrent = |json: &str| -> Torrent { serde_json::from_str(json).unwrap() };
+        let moving = RelocationState::Moving;
+        let second = Duration::from_secs(1);
+
+        let old_dir = torrent(r#"{"hashString": "AB", "downloadDir": "/data/tv", "status": 0}"#);
+        assert_eq!(
+            relocator.next_state(&moving, &relocation, &old_dir, None, second),
+            moving
+        );
+        assert_eq!(
+            relocator.next_state(&moving, &relocation, &old_dir, None, Duration::from_secs(7200)),
+            RelocationState::Failed("Move not done after 3600s".to_string())
+        );
+        let moved = torrent(r#"{"hashString": "AB", "downloadDir": "/mnt/big/", "status": 6}"#);
+        assert_eq!(
+            relocator.next_state(&moving, &relocation, &moved, None, second),
+            RelocationState::Verifying
+        );
+        let checking = torrent(r#"{"hashString": "AB", "downloadDir": "/mnt/big", "status": 2}"#);
+        let verifying = RelocationState::Verifying;
+        assert_eq!(
+            relocator.next_state(&verifying, &relocation, &checking, None, second),
+            verifying
+        );
+        assert_eq!(
+            relocator.next_state(&verifying, &relocation, &moved, None, second),
+            RelocationState::Done
+        );
+        let errored = torrent(r#"{"hashString": "AB", "error": 3, "errorString": "No data found"}"#);
+        assert_eq!(
+            relocator.next_state(&verifying, &relocation, &errored, None, second),
+            RelocationState::Failed("No data found".to_string())
+        );
+        // the error the torrent had before the move does not fail it
+        let errored_before = torrent(
+            r#"{"hashString": "AB", "downloadDir": "/data/tv", "status": 0, "error": 3,
+                "errorString": "No data found"}"#,
+        );
+        assert_eq!(
+            relocator.next_state(&moving, &relocation, &errored_before, Some("No data found"), second),
+            moving
+        );
+        assert_eq!(
+            relocator.next_state(&moving, &relocation, &errored_before, Some("Permission denied"), second),
+            RelocationState::Failed("No data found".to_string())
+        );
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::types::{File, Id, Result};
+use crate::TransClient;
+
+/// Which paths of a torrent a plan renames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Files,
+    /// folders, including the root folder of a multi-file torrent
+    Folders,
+    All,
+}
+
+/// One torrent-rename-path call: the last component of `path` becomes `name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathRename {
+    pub path: String,
+    pub name: String,
+}
+
+/// Paths which would end up the same, or a rename onto a path still in use when it is sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub paths: Vec<String>,
+    pub target: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rename conflict: {} -> {}", self.paths.join(", "), self.target)
+    }
+}
+
+impl Error for Conflict {}
+
+/// Renames of the files and folders of a torrent, checked for conflicts and ordered
+/// so that every call uses paths as they are when it is sent: deepest paths first,
+/// parent folders last, and a rename onto a sibling after the sibling's own rename.
+///
+/// ```
+/// use transmission_rpc::rename::{RenamePlan, Target};
+/// use transmission_rpc::types::File;
+/// # use transmission_rpc::types::Result;
+///
+/// # fn main() -> Result<()> {
+/// let files = vec![File {
+///     length: 1,
+///     bytes_completed: 1,
+///     name: "Show.S01.1080p-GRP/show.s01e01.1080p-grp.mkv".to_string(),
+/// }];
+/// let plan = RenamePlan::scene(&files, Target::Files, "{title} S{season}E{episode}{ext}")?
+///     .and(RenamePlan::root(&files, "Show - Season 1")?)?;
+/// assert_eq!(plan.preview(&files), vec!["Show - Season 1/show S01E01.mkv"]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenamePlan {
+    /// files and folders of the torrent, true for files
+    nodes: BTreeMap<String, bool>,
+    /// new last component by original path
+    edits: BTreeMap<String, String>,
+    /// in execution order
+    pub renames: Vec<PathRename>,
+}
+
+impl RenamePlan {
+    /// Renames each path of `target` to what `rename` returns for its last component,
+    /// given with whether it is a file; None or the same name keeps it
+    ///
+    /// # Errors
+    ///
+    /// Invalid new name, or `Conflict`
+    pub fn map<F: Fn(&str, bool) -> Option<String>>(files: &[File], target: Target, rename: F) -> Result<RenamePlan> {
+        let mut edits = BTreeMap::new();
+        let nodes = nodes(files);
+        for (path, &is_file) in &nodes {
+            let wanted = match target {
+                Target::Files => is_file,
+                Target::Folders => !is_file,
+                Target::All => true,
+            };
+            if !wanted {
+                continue;
+            }
+            let name = last_component(path);
+            if let Some(new_name) = rename(name, is_file).filter(|it| it != name) {
+                edits.insert(path.clone(), new_name);
+            }
+        }
+        RenamePlan::new(nodes, edits)
+    }
+
+    /// Replaces the matches of `regex` in the names of the paths of `target`,
+    /// `replacement` can refer to groups, e.g. `$1`
+    ///
+    /// # Errors
+    ///
+    /// Invalid regex or new name, or `Conflict`
+    pub fn regex(files: &[File], target: Target, regex: &str, replacement: &str) -> Result<RenamePlan> {
+        let regex = Regex::new(regex)?;
+        RenamePlan::map(files, target, |name, _| {
+            Some(regex.replace_all(name, replacement).into_owned())
+        })
+    }
+
+    /// Renames the paths of `target` from their scene name, see `SceneName::render`
+    /// for the template; names missing a value of the template are kept
+    ///
+    /// # Errors
+    ///
+    /// Invalid new name, or `Conflict`
+    pub fn scene(files: &[File], target: Target, template: &str) -> Result<RenamePlan> {
+        RenamePlan::map(files, target, |name, is_file| {
+            SceneName::parse(name, is_file).render(template)
+        })
+    }
+
+    /// Renames the root folder of the torrent, or its file if it has one file only.
+    /// The torrent's name follows.
+    ///
+    /// # Errors
+    ///
+    /// Invalid new name
+    pub fn root(files: &[File], name: &str) -> Result<RenamePlan> {
+        let root = files
+            .first()
+            .and_then(|it| it.name.split('/').next())
+            .ok_or("Torrent has no files")?;
+        let mut edits = BTreeMap::new();
+        if root != name {
+            edits.insert(root.to_string(), name.to_string());
+        }
+        RenamePlan::new(nodes(files), edits)
+    }
+
+    /// Both plans of the same torrent, as one
+    ///
+    /// # Errors
+    ///
+    /// Both plans rename the same path differently, or `Conflict`
+    pub fn and(self, other: RenamePlan) -> Result<RenamePlan> {
+        let mut edits = self.edits;
+        for (path, name) in other.edits {
+            match edits.get(&path) {
+                Some(existing) if *existing != name => {
+                    return Err(From::from(format!("{} renamed to both {} and {}", path, existing, name)));
+                }
+                _ => edits.insert(path, name),
+            };
+        }
+        RenamePlan::new(self.nodes, edits)
+    }
+
+    /// The file names of the torrent once the plan is applied
+    pub fn preview(&self, files: &[File]) -> Vec<String> {
+        files.iter().map(|it| new_path(&it.name, &self.edits)).collect()
+    }
+
+    /// Sends the renames to the daemon, in order
+    ///
+    /// # Errors
+    ///
+    /// Any error calling the daemon, or a rename the daemon refused; the renames before it are applied
+    pub async fn apply(&self, client: &mut TransClient, id: Id) -> Result<()> {
+        for rename in &self.renames {
+            let response = client
+                .torrent_rename_path(vec![id.clone()], rename.path.clone(), rename.name.clone())
+                .await?;
+            if !response.is_ok() {
+                return Err(From::from(format!(
+                    "Unable to rename {} to {}: {}",
+                    rename.path, rename.name, response.result
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn new(nodes: BTreeMap<String, bool>, edits: BTreeMap<String, String>) -> Result<RenamePlan> {
+        for (path, name) in &edits {
+            if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+                return Err(From::from(format!("Invalid new name {:?} of {}", name, path)));
+            }
+        }
+        let mut renames: Vec<PathRename> = edits
+            .iter()
+            .map(|(path, name)| PathRename {
+                path: path.clone(),
+                name: name.clone(),
+            })
+            .collect();
+        // deepest first, so the parents in each path are not renamed yet
+        renames.sort_by(|a, b| depth(&b.path).cmp(&depth(&a.path)).then(a.path.cmp(&b.path)));
+        let mut plan = RenamePlan { nodes, edits, renames };
+        plan.order()?;
+        Ok(plan)
+    }
+
+    /// Fails on paths ending up the same. Orders renames onto a sibling renamed as well after it,
+    /// e.g. `b -> c` before `a -> b`, and fails when there is no such order, e.g. on a swap.
+    fn order(&mut self) -> Result<()> {
+        let mut targets: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for path in self.nodes.keys() {
+            targets.entry(new_path(path, &self.edits)).or_default().push(path.clone());
+        }
+        if let Some((target, paths)) = targets.into_iter().find(|(_, paths)| paths.len() > 1) {
+            return Err(Box::new(Conflict { paths, target }));
+        }
+        let mut current: BTreeSet<String> = self.nodes.keys().cloned().collect();
+        let mut pending = std::mem::take(&mut self.renames);
+        while !pending.is_empty() {
+            // the first rename of the deepest ones whose target is free
+            let deepest = depth(&pending[0].path);
+            let next = pending
+                .iter()
+                .take_while(|it| depth(&it.path) == deepest)
+                .position(|it| !current.contains(&it.target()));
+            let rename = match next {
+                Some(i) => pending.remove(i),
+                None => {
+                    return Err(Box::new(Conflict {
+                        paths: vec![pending[0].path.clone()],
+                        target: pending[0].target(),
+                    }))
+                }
+            };
+            let target = rename.target();
+            let prefix = format!("{}/", rename.path);
+            let moved: Vec<String> = current
+                .iter()
+                .filter(|it| **it == rename.path || it.starts_with(&prefix))
+                .cloned()
+                .collect();
+            for path in moved {
+                current.remove(&path);
+                current.insert(format!("{}{}", target, &path[rename.path.len()..]));
+            }
+            self.renames.push(rename);
+        }
+        Ok(())
+    }
+}
+
+impl PathRename {
+    /// `path` once renamed
+    fn target(&self) -> String {
+        match self.path.rsplit_once('/') {
+            Some((parent, _)) => format!("{}/{}", parent, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The files and folders of a torrent, true for files
+fn nodes(files: &[File]) -> BTreeMap<String, bool> {
+    let mut nodes = BTreeMap::new();
+    for file in files {
+        let mut end = 0;
+        while let Some(i) = file.name[end..].find('/') {
+            end += i;
+            nodes.entry(file.name[..end].to_string()).or_insert(false);
+            end += 1;
+        }
+        nodes.insert(file.name.clone(), true);
+    }
+    nodes
+}
+
+fn last_component(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn depth(path: &str) -> usize {
+    path.matches('/').count()
+}
+
+/// `path` with each of its components renamed as planned
+fn new_path(path: &str, edits: &BTreeMap<String, String>) -> String {
+    let mut original = String::new();
+    let mut renamed = vec![];
+    for component in path.split('/') {
+        if !original.is_empty() {
+            original.push('/');
+        }
+        original.push_str(component);
+        renamed.push(edits.get(&original).map(String::as_str).unwrap_or(component));
+    }
+    renamed.join("/")
+}
+
+/// What a scene release name tells, e.g. `The.Show.S01E02.1080p.WEB.h264-GROUP.mkv`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SceneName {
+    /// words before the year, episode or resolution, e.g. `The Show`
+    pub title: Option<String>,
+    pub year: Option<u32>,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    /// e.g. `1080p`
+    pub resolution: Option<String>,
+    pub group: Option<String>,
+    /// extension of a file, with its dot
+    pub extension: Option<String>,
+}
+
+impl SceneName {
+    pub fn parse(name: &str, is_file: bool) -> SceneName {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, ext))
+                if is_file && !ext.is_empty() && ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric()) =>
+            {
+                (stem, Some(format!(".{}", ext)))
+            }
+            _ => (name, None),
+        };
+        static YEAR: OnceLock<Regex> = OnceLock::new();
+        static EPISODE: OnceLock<Regex> = OnceLock::new();
+        static RESOLUTION: OnceLock<Regex> = OnceLock::new();
+        static GROUP: OnceLock<Regex> = OnceLock::new();
+        let captures = |regex: &'static OnceLock<Regex>, re: &str| {
+            regex.get_or_init(|| Regex::new(re).unwrap()).captures(stem)
+        };
+        let year = captures(&YEAR, r"\b(19\d{2}|20\d{2})\b");
+        let episode = captures(&EPISODE, r"(?i)\bs(\d{1,2})[ ._]?e(\d{1,3})\b");
+        let resolution = captures(&RESOLUTION, r"(?i)\b(\d{3,4}p|4k)\b");
+        let group = captures(&GROUP, r"-([A-Za-z0-9]+)$").map(|it| it[1].to_string());
+        let end = [&year, &episode, &resolution]
+            .iter()
+            .filter_map(|it| it.as_ref().and_then(|it| it.get(0)).map(|it| it.start()))
+            .min()
+            .unwrap_or(stem.len());
+        let title = stem[..end]
+            .split(['.', '_', ' ', '[', ']', '(', ')'])
+            .filter(|it| !it.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end_matches(" -")
+            .to_string();
+        SceneName {
+            title: Some(title).filter(|it| !it.is_empty()),
+            year: year.and_then(|it| it[1].parse().ok()),
+            season: episode.as_ref().and_then(|it| it[1].parse().ok()),
+            episode: episode.as_ref().and_then(|it| it[2].parse().ok()),
+            resolution: resolution.map(|it| it[1].to_lowercase()),
+            group,
+            extension,
+        }
+    }
+
+    /// Fills `{title}`, `{year}`, `{season}`, `{episode}` (both two digits at least),
+    /// `{resolution}`, `{group}` and `{ext}` in `template`; None if one of them is unknown.
+    /// `{ext}` of a folder is empty.
+    pub fn render(&self, template: &str) -> Option<String> {
+        let values = [
+            ("{title}", self.title.clone()),
+            ("{year}", self.year.map(|it| it.to_string())),
+            ("{season}", self.season.map(|it| format!("{:02}", it))),
+            ("{episode}", self.episode.map(|it| format!("{:02}", it))),
+            ("{resolution}", self.resolution.clone()),
+            ("{group}", self.group.clone()),
+            ("{ext}", Some(self.extension.clone().unwrap_or_default())),
+        ];
+        let mut rendered = template.to_string();
+        for (key, value) in values {
+            if rendered.contains(key) {
+                rendered = rendered.replace(key, &value?);
+            }
+        }
+        Some(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<File> {
+        names
+            .iter()
+            .map(|it| File {
+                length: 1,
+                bytes_completed: 0,
+                name: it.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_scene_name() {
+        let name = SceneName::parse("The.Show.S01E02.1080p.WEB.h264-GROUP.mkv", true);
+        assert_eq!(
+            name,
+            SceneName {
+                title: Some("The Show".to_string()),
+                year: None,
+                season: Some(1),
+                episode: Some(2),
+                resolution: Some("1080p".to_string()),
+                group: Some("GROUP".to_string()),
+                extension: Some(".mkv".to_string()),
+            }
+        );
+        assert_eq!(
+            name.render("{title} - S{season}E{episode}{ext}").as_deref(),
+            Some("The Show - S01E02.mkv")
+        );
+        assert_eq!(name.render("{title} ({year})"), None);
+
+        let movie = SceneName::parse("Some_Movie_(2019)_[2160p]", false);
+        assert_eq!(movie.title.as_deref(), Some("Some Movie"));
+        assert_eq!(movie.render("{title} ({year}){ext}").as_deref(), Some("Some Movie (2019)"));
+    }
+
+    #[test]
+    pub fn test_plan_order() -> Result<()> {
+        let files = files(&["Root/Sub/a.txt", "Root/Sub/b.txt", "Root/c.txt"]);
+        let plan = RenamePlan::regex(&files, Target::All, r"\.txt$", ".md")?
+            .and(RenamePlan::regex(&files, Target::Folders, "^Sub$", "Folder")?)?
+            .and(RenamePlan::root(&files, "New Root")?)?;
+        let order: Vec<(&str, &str)> = plan
+            .renames
+            .iter()
+            .map(|it| (it.path.as_str(), it.name.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("Root/Sub/a.txt", "a.md"),
+                ("Root/Sub/b.txt", "b.md"),
+                ("Root/Sub", "Folder"),
+                ("Root/c.txt", "c.md"),
+                ("Root", "New Root"),
+            ]
+        );
+        assert_eq!(
+            plan.preview(&files),
+            vec!["New Root/Folder/a.md", "New Root/Folder/b.md", "New Root/c.md"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_conflicts() {
+        let files = files(&["Root/a.txt", "Root/A.TXT", "Root/b.txt"]);
+        let error = RenamePlan::map(&files, Target::Files, |name, _| Some(name.to_lowercase())).unwrap_err();
+        assert_eq!(error.to_string(), "Rename conflict: Root/A.TXT, Root/a.txt -> Root/a.txt");
+        // a rename onto a path still in use when it is sent
+        let swap = RenamePlan::map(&files, Target::Files, |name, _| match name {
+            "a.txt" => Some("b.txt".to_string()),
+            "b.txt" => Some("a.txt".to_string()),
+            _ => None,
+        });
+        assert!(swap.unwrap_err().to_string().starts_with("Rename conflict: Root/a.txt -> Root/b.txt"));
+        // a chain is sent from its end
+        let chain = RenamePlan::map(&files, Target::Files, |name, _| match name {
+            "a.txt" => Some("b.txt".to_string()),
+            "b.txt" => Some("c.txt".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        let order: Vec<&str> = chain.renames.iter().map(|it| it.path.as_str()).collect();
+        assert_eq!(order, vec!["Root/b.txt", "Root/a.txt"]);
+        assert_eq!(chain.preview(&files), vec!["Root/b.txt", "Root/A.TXT", "Root/c.txt"]);
+        assert!(RenamePlan::regex(&files, Target::Files, "a", "x/y").is_err());
+        assert!(RenamePlan::root(&files, "Root").unwrap().renames.is_empty());
+        let both = RenamePlan::root(&files, "X").unwrap().and(RenamePlan::root(&files, "Y").unwrap());
+        assert!(both.is_err());
+        let to_c = |from: &'static str| {
+            move |name: &str, _| if name == from { Some("c.txt".to_string()) } else { None }
+        };
+        let a_to_c = RenamePlan::map(&files, Target::Files, to_c("a.txt")).unwrap();
+        let b_to_c = RenamePlan::map(&files, Target::Files, to_c("b.txt")).unwrap();
+        assert!(a_to_c.and(b_to_c).is_err());
+    }
+}
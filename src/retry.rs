@@ -0,0 +1,190 @@
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+/// Describes how `TransClient` retries calls that failed because of a transient error,
+/// e.g. a connection reset, a timeout or a 5xx response while the daemon is restarting.
+///
+/// Calls that are not idempotent (like `torrent-add`) are only retried
+/// when the request could not reach the daemon at all.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// how many times a failed call is repeated, 0 disables retries
+    pub max_retries: usize,
+    /// delay before the first retry
+    pub initial_backoff: Duration,
+    /// upper bound for the delay between two retries
+    pub max_backoff: Duration,
+    /// factor the delay grows by after every retry
+    pub multiplier: f64,
+    /// randomize each delay between half and the full computed value
+    pub jitter: bool,
+    /// retry non-idempotent calls on any transient error as well
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns a policy that never retries
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Returns the delay before the retry number `attempt` (starting from 0)
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.min(i32::MAX as usize) as i32);
+        let backoff = self.initial_backoff.as_secs_f64() * factor;
+        let backoff = Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()));
+        if self.jitter {
+            backoff.mul_f64(0.5 + random_fraction() / 2.0)
+        } else {
+            backoff
+        }
+    }
+
+    /// Tells if a call should be repeated after its `attempt`-th retry failed
+    pub(crate) fn should_retry(&self, attempt: usize, idempotent: bool, failure: &Failure) -> bool {
+        if attempt >= self.max_retries {
+            return false;
+        }
+        if idempotent || self.retry_non_idempotent {
+            failure.is_transient()
+        } else {
+            failure.is_not_sent()
+        }
+    }
+}
+
+/// A failed attempt to perform a call
+pub(crate) enum Failure<'a> {
+    Transport(&'a (dyn Error + Send + Sync + 'static)),
+    Status(StatusCode),
+}
+
+impl Failure<'_> {
+    fn is_transient(&self) -> bool {
+        match self {
+            Failure::Transport(e) => is_connect(*e) || is_interrupted(*e),
+            Failure::Status(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+        }
+    }
+
+    /// The daemon has not received the request, so it is safe to send it again
+    fn is_not_sent(&self) -> bool {
+        match self {
+            Failure::Transport(e) => is_connect(*e),
+            Failure::Status(_) => false,
+        }
+    }
+}
+
+/// The connection to the daemon could not be established
+fn is_connect(e: &(dyn Error + 'static)) -> bool {
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return e.is_connect();
+    }
+    #[cfg(unix)]
+    if let Some(e) = e.downcast_ref::<hyper::Error>() {
+        return e.is_connect();
+    }
+    if let Some(e) = e.downcast_ref::<io::Error>() {
+        return matches!(e.kind(), io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound);
+    }
+    false
+}
+
+/// The connection was lost or timed out while the request was in flight
+fn is_interrupted(e: &(dyn Error + 'static)) -> bool {
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return e.is_timeout() || e.is_request();
+    }
+    #[cfg(unix)]
+    if let Some(e) = e.downcast_ref::<hyper::Error>() {
+        return e.is_incomplete_message() || e.is_closed() || e.is_timeout();
+    }
+    if let Some(e) = e.downcast_ref::<io::Error>() {
+        return matches!(
+            e.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+        );
+    }
+    false
+}
+
+/// Returns a random number in [0, 1)
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_backoff_grows_up_to_max() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(10), Duration::from_secs(10));
+    }
+
+    #[test]
+    pub fn test_backoff_jitter_stays_in_bounds() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..5 {
+            let backoff = policy.backoff(attempt);
+            let max = RetryPolicy { jitter: false, ..policy.clone() }.backoff(attempt);
+            assert!(backoff <= max);
+            assert!(backoff >= max / 2);
+        }
+    }
+
+    #[test]
+    pub fn test_non_idempotent_calls_are_not_retried_on_server_errors() {
+        let policy = RetryPolicy::default();
+        let failure = Failure::Status(StatusCode::SERVICE_UNAVAILABLE);
+        assert!(policy.should_retry(0, true, &failure));
+        assert!(!policy.should_retry(0, false, &failure));
+        assert!(!policy.should_retry(3, true, &failure));
+        assert!(!RetryPolicy::none().should_retry(0, true, &failure));
+    }
+
+    #[test]
+    pub fn test_connection_refused_is_retried_for_non_idempotent_calls() {
+        let policy = RetryPolicy::default();
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(policy.should_retry(0, false, &Failure::Transport(&refused)));
+        assert!(!policy.should_retry(0, false, &Failure::Transport(&reset)));
+        assert!(policy.should_retry(0, true, &Failure::Transport(&reset)));
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::filter::TorrentFilter;
+use crate::state;
+use crate::types::{Id, Nothing, Result, RpcResponse, SessionSetArgs, TorrentAction, TorrentGetField};
+use crate::TransClient;
+
+/// What a job does when it runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduledAction {
+    /// turns the alternative speed limits on or off
+    AltSpeed(bool),
+    /// starts the torrents matching the filter
+    Start(TorrentFilter),
+    /// stops the torrents matching the filter
+    Stop(TorrentFilter),
+    BlocklistUpdate,
+    ReannounceStalled,
+}
+
+/// An action run on a cron schedule
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub name: String,
+    schedule: cron::Schedule,
+    pub action: ScheduledAction,
+}
+
+impl Job {
+    /// Returns a job running `action` on the cron `expression`: either the usual
+    /// `minute hour day-of-month month day-of-week`, or the same preceded by seconds.
+    /// Days of the week are numbered from 0 (Sunday) to 6, 7 being Sunday too,
+    /// or named, e.g. `0 9 * * 1-5` and `0 9 * * Mon-Fri` are the same.
+    ///
+    /// # Errors
+    ///
+    /// Invalid cron expression
+    pub fn new(name: &str, expression: &str, action: ScheduledAction) -> Result<Job> {
+        let invalid = |e: &dyn std::fmt::Display| format!("Invalid schedule of job {}: {}", name, e);
+        let mut fields: Vec<String> = expression.split_whitespace().map(str::to_string).collect();
+        if fields.len() == 5 {
+            fields.insert(0, "0".to_string());
+        }
+        if let Some(weekdays) = fields.get_mut(5) {
+            *weekdays = cron_weekdays(weekdays).map_err(|e| invalid(&e))?;
+        }
+        let schedule = cron::Schedule::from_str(&fields.join(" ")).map_err(|e| invalid(&e))?;
+        Ok(Job {
+            name: name.to_string(),
+            schedule,
+            action,
+        })
+    }
+
+    /// First time the job runs strictly after `time`, in `timezone`
+    fn next_after(&self, time: i64, timezone: Tz) -> Option<i64> {
+        let time = timezone.timestamp_opt(time, 0).single()?;
+        self.schedule.after(&time).next().map(|it| it.timestamp())
+    }
+}
+
+/// Translates the usual day of the week numbers, Sunday being 0 or 7, to the ones of the
+/// `cron` crate, Sunday being 1; named days are kept
+fn cron_weekdays(field: &str) -> std::result::Result<String, String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_string());
+    }
+    let mut parts = vec![];
+    for part in field.split(',') {
+        if part.chars().any(|c| c.is_ascii_alphabetic()) {
+            parts.push(part.to_string());
+            continue;
+        }
+        let invalid = || format!("invalid day of the week {}", part);
+        let number = |it: &str| it.parse::<u32>().ok().filter(|it| *it <= 7).ok_or_else(invalid);
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<usize>().ok().filter(|it| *it > 0).ok_or_else(invalid)?)),
+            None => (part, None),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (0, 6),
+            Some((first, last)) => (number(first)?, number(last)?),
+            None if step.is_some() => (number(range)?, 6),
+            None => (number(range)?, number(range)?),
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        for day in (first..=last).step_by(step.unwrap_or(1)) {
+            let day = (day % 7 + 1).to_string();
+            if !parts.contains(&day) {
+                parts.push(day);
+            }
+        }
+    }
+    Ok(parts.join(","))
+}
+
+/// A job which ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobRun {
+    pub job: String,
+    /// unix timestamp
+    pub time: i64,
+    /// number of torrents acted on, or why the daemon refused the action
+    pub outcome: std::result::Result<usize, String>,
+}
+
+/// What the scheduler remembers between ticks and restarts
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SchedulerState {
+    /// unix timestamp of the last run of each job, by name
+    last_run: BTreeMap<String, i64>,
+}
+
+/// Runs jobs when their schedule is due.
+///
+/// A job missed while the scheduler was down runs once on the next tick if the
+/// scheduler has a state file; a job never seen before starts counting from its first tick.
+///
+/// ```
+/// use transmission_rpc::filter::TorrentFilter;
+/// use transmission_rpc::scheduler::{Job, ScheduledAction, Scheduler};
+/// # use transmission_rpc::types::Result;
+///
+/// # fn main() -> Result<()> {
+/// // pause label "backup" on weekdays from 9 to 17
+/// let backup = TorrentFilter::Label("backup".to_string());
+/// let jobs = vec![
+///     Job::new("pause backup", "0 9 * * Mon-Fri", ScheduledAction::Stop(backup.clone()))?,
+///     Job::new("resume backup", "0 17 * * Mon-Fri", ScheduledAction::Start(backup))?,
+///     Job::new("blocklist", "30 3 * * *", ScheduledAction::BlocklistUpdate)?,
+///     Job::new("reannounce", "*/15 * * * *", ScheduledAction::ReannounceStalled)?,
+/// ];
+/// let mut scheduler = Scheduler::with_state_file(jobs, "scheduler.json".into())?;
+/// scheduler.set_timezone("Europe/Berlin")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    timezone: Tz,
+    state_path: Option<PathBuf>,
+    state: SchedulerState,
+}
+
+impl Scheduler {
+    /// Returns a scheduler reading its jobs' schedules in UTC
+    pub fn new(jobs: Vec<Job>) -> Scheduler {
+        Scheduler {
+            jobs,
+            timezone: Tz::UTC,
+            state_path: None,
+            state: SchedulerState::default(),
+        }
+    }
+
+    /// Returns a scheduler keeping its state in `path`, loading it if the file exists
+    ///
+    /// # Errors
+    ///
+    /// IO Error or Deserialization error of an existing state file
+    pub fn with_state_file(jobs: Vec<Job>, path: PathBuf) -> Result<Scheduler> {
+        let state = state::load(&path)?;
+        Ok(Scheduler {
+            state_path: Some(path),
+            state,
+            ..Scheduler::new(jobs)
+        })
+    }
+
+    /// Sets the timezone of the schedules by its IANA name, e.g. `Europe/Berlin`
+    ///
+    /// # Errors
+    ///
+    /// Unknown timezone
+    pub fn set_timezone(&mut self, name: &str) -> Result<()> {
+        self.timezone = name
+            .parse()
+            .map_err(|_| format!("Unknown timezone {}", name))?;
+        Ok(())
+    }
+
+    /// Unix timestamp of the next time a job is due
+    pub fn next_run(&self) -> Option<i64> {
+        let now = Utc::now().timestamp();
+        self.jobs
+            .iter()
+            .filter_map(|job| {
+                let last = self.state.last_run.get(&job.name).copied().unwrap_or(now);
+                job.next_after(last, self.timezone)
+            })
+            .min()
+    }
+
+    /// Indexes of the jobs due at `now`; jobs seen for the first time are recorded as run
+    fn due(&mut self, now: i64) -> Vec<usize> {
+        let mut due = vec![];
+        for (i, job) in self.jobs.iter().enumerate() {
+            let last = *self.state.last_run.entry(job.name.clone()).or_insert(now);
+            if job.next_after(last, self.timezone).is_some_and(|it| it <= now) {
+                due.push(i);
+            }
+        }
+        due
+    }
+
+    /// Runs the jobs due now, then saves the state.
+    /// A job whose action the daemon refused counts as run.
+    ///
+    /// # Errors
+    ///
+    /// Any error calling the daemon, the job is run again on the next tick;
+    /// or IO Error of the state file
+    pub async fn tick(&mut self, client: &mut TransClient) -> Result<Vec<JobRun>> {
+        let now = Utc::now().timestamp();
+        let mut runs = vec![];
+        for i in self.due(now) {
+            let job = &self.jobs[i];
+            let outcome = match run_action(&job.action, client).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    self.save()?;
+                    return Err(e);
+                }
+            };
+            self.state.last_run.insert(job.name.clone(), now);
+            runs.push(JobRun {
+                job: job.name.clone(),
+                time: now,
+                outcome,
+            });
+        }
+        self.save()?;
+        Ok(runs)
+    }
+
+    /// Ticks whenever a job is due and logs the runs.
+    /// Wakes up at least every minute, so clock changes are noticed.
+    ///
+    /// # Errors
+    ///
+    /// The error of the first tick that fails, e.g. on an unreachable daemon;
+    /// jobs the daemon refuses are logged only
+    pub async fn watch(&mut self, client: &mut TransClient) -> Result<()> {
+        loop {
+            for run in self.tick(client).await? {
+                match &run.outcome {
+                    Ok(count) => info!("Ran {} on {} torrents", run.job, count),
+                    Err(e) => warn!("Job {} failed: {}", run.job, e),
+                }
+            }
+            let now = Utc::now().timestamp();
+            let wait = self.next_run().map_or(60, |it| (it - now).clamp(1, 60));
+            tokio::time::sleep(Duration::from_secs(wait as u64)).await;
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        state::save(self.state_path.as_deref(), &self.state)
+    }
+}
+
+async fn run_action(
+    action: &ScheduledAction,
+    client: &mut TransClient,
+) -> Result<std::result::Result<usize, String>> {
+    let (torrent_action, filter) = match action {
+        ScheduledAction::AltSpeed(enabled) => {
+            let args = SessionSetArgs {
+                alt_speed_enabled: Some(*enabled),
+                ..SessionSetArgs::default()
+            };
+            return Ok(outcome(client.session_set(args).await?, 0));
+        }
+        ScheduledAction::BlocklistUpdate => {
+            let response = client.blocklist_update().await?;
+            return Ok(if response.is_ok() {
+                Ok(0)
+            } else {
+                Err(response.result)
+            });
+        }
+        ScheduledAction::Start(filter) => (TorrentAction::Start, filter.clone()),
+        ScheduledAction::Stop(filter) => (TorrentAction::Stop, filter.clone()),
+        ScheduledAction::ReannounceStalled => (TorrentAction::Reannounce, TorrentFilter::IsStalled),
+    };
+    let mut fields = vec![TorrentGetField::Id, TorrentGetField::HashString];
+    fields.extend(filter.fields());
+    let response = client.torrent_get(Some(fields), None).await?;
+    if !response.is_ok() {
+        return Ok(Err(response.result));
+    }
+    let ids: Vec<Id> = response
+        .arguments
+        .torrents
+        .iter()
+        .filter(|it| filter.matches(it))
+        .filter_map(|it| match (&it.hash_string, it.id) {
+            (Some(hash), _) => Some(Id::Hash(hash.to_lowercase())),
+            (None, Some(id)) => Some(Id::Id(id)),
+            _ => None,
+        })
+        .collect();
+    if ids.is_empty() {
+        return Ok(Ok(0));
+    }
+    let count = ids.len();
+    Ok(outcome(client.torrent_action(torrent_action, ids).await?, count))
+}
+
+fn outcome(response: RpcResponse<Nothing>, count: usize) -> std::result::Result<usize, String> {
+    if response.is_ok() {
+        Ok(count)
+    } else {
+        Err(response.result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn timestamp(date: &str) -> i64 {
+        DateTime::parse_from_rfc3339(date).unwrap().timestamp()
+    }
+
+    #[test]
+    pub fn test_schedule() -> Result<()> {
+        let weekdays = Job::new("pause", "0 9 * * Mon-Fri", ScheduledAction::AltSpeed(true))?;
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        // Friday 2024-03-01 10:00 in Berlin, next run is Monday 9:00 Berlin time
+        assert_eq!(
+            weekdays.next_after(timestamp("2024-03-01T09:00:00Z"), berlin),
+            Some(timestamp("2024-03-04T08:00:00Z"))
+        );
+        assert_eq!(
+            weekdays.next_after(timestamp("2024-03-01T09:00:00Z"), Tz::UTC),
+            Some(timestamp("2024-03-04T09:00:00Z"))
+        );
+        let seconds = Job::new("often", "30 */5 * * * *", ScheduledAction::BlocklistUpdate)?;
+        assert_eq!(
+            seconds.next_after(timestamp("2024-03-01T09:00:00Z"), Tz::UTC),
+            Some(timestamp("2024-03-01T09:00:30Z"))
+        );
+        assert!(Job::new("invalid", "61 * * * *", ScheduledAction::BlocklistUpdate).is_err());
+        assert!(Job::new("invalid", "0 9 * * 8", ScheduledAction::BlocklistUpdate).is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_weekdays() -> Result<()> {
+        // days firing in the week from Sunday 2024-03-03
+        let days = |expression: &str| -> Result<Vec<String>> {
+            let job = Job::new("days", expression, ScheduledAction::BlocklistUpdate)?;
+            let mut time = timestamp("2024-03-02T23:00:00Z");
+            let mut days = vec![];
+            while let Some(next) = job.next_after(time, Tz::UTC).filter(|it| *it < timestamp("2024-03-10T00:00:00Z")) {
+                days.push(DateTime::from_timestamp(next, 0).unwrap().format("%a").to_string());
+                time = next;
+            }
+            Ok(days)
+        };
+        assert_eq!(days("0 9 * * 1-5")?, vec!["Mon", "Tue", "Wed", "Thu", "Fri"]);
+        assert_eq!(days("0 9 * * Mon-Fri")?, vec!["Mon", "Tue", "Wed", "Thu", "Fri"]);
+        assert_eq!(days("0 9 * * 0,6")?, vec!["Sun", "Sat"]);
+        assert_eq!(days("0 9 * * 7")?, vec!["Sun"]);
+        assert_eq!(days("0 0 9 * * */3")?, vec!["Sun", "Wed", "Sat"]);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_due() -> Result<()> {
+        let jobs = vec![
+            Job::new("morning", "0 9 * * *", ScheduledAction::AltSpeed(true))?,
+            Job::new("evening", "0 17 * * *", ScheduledAction::AltSpeed(false))?,
+        ];
+        let mut scheduler = Scheduler::new(jobs);
+        scheduler.set_timezone("Europe/Berlin")?;
+        assert!(scheduler.set_timezone("Mars/Olympus").is_err());
+
+        // first tick only records the jobs
+        assert!(scheduler.due(timestamp("2024-03-01T07:00:00Z")).is_empty());
+        assert!(scheduler.due(timestamp("2024-03-01T07:59:59Z")).is_empty());
+        assert_eq!(scheduler.due(timestamp("2024-03-01T08:00:00Z")), vec![0]);
+
+        // nothing ran: a job missed for days is due once
+        assert_eq!(scheduler.due(timestamp("2024-03-05T12:00:00Z")), vec![0, 1]);
+        scheduler.state.last_run.insert("morning".to_string(), timestamp("2024-03-05T12:00:00Z"));
+        assert_eq!(scheduler.due(timestamp("2024-03-05T12:00:01Z")), vec![1]);
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::types::Result;
+
+/// Reads the JSON state in `path`, or the default state if the file does not exist yet
+pub(crate) fn load<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    if path.exists() {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    } else {
+        Ok(T::default())
+    }
+}
+
+/// Writes `state` as JSON to `path`, if there is one.
+/// The state is written next to it then renamed, so a crash never leaves a truncated state.
+pub(crate) fn save<T: Serialize>(path: Option<&Path>, state: &T) -> Result<()> {
+    if let Some(path) = path {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string(state)?)?;
+        fs::rename(&tmp, path)?;
+    }
+    Ok(())
+}
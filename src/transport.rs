@@ -0,0 +1,135 @@
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode};
+
+use crate::types::{BasicAuth, Result};
+
+pub(crate) const SESSION_ID_HEADER: &str = "X-Transmission-Session-Id";
+
+/// Path of the RPC endpoint when the daemon listens on a Unix socket
+#[cfg(unix)]
+const UNIX_RPC_PATH: &str = "/transmission/rpc";
+
+/// The way requests reach the daemon
+pub(crate) enum Transport {
+    /// an HTTP(S) url, e.g. `http://localhost:9091/transmission/rpc`
+    Http { client: Client, url: String },
+    /// a Unix socket, given as `unix:/path/to/socket` or `unix:///path/to/socket`
+    #[cfg(unix)]
+    Unix {
+        client: hyper::Client<hyperlocal::UnixConnector>,
+        path: std::path::PathBuf,
+    },
+}
+
+/// What the daemon answered to a request
+pub(crate) struct RawResponse {
+    pub status: StatusCode,
+    pub session_id: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Transport {
+    pub fn new(url: &str) -> Transport {
+        #[cfg(unix)]
+        if let Some(path) = url.strip_prefix("unix:") {
+            let path = path.strip_prefix("//").unwrap_or(path);
+            return Transport::Unix {
+                client: hyper::Client::builder().build(hyperlocal::UnixConnector),
+                path: std::path::PathBuf::from(path),
+            };
+        }
+        Transport::Http {
+            client: Client::new(),
+            url: url.to_string(),
+        }
+    }
+
+    /// Sends a JSON `body` to the RPC endpoint
+    ///
+    /// # Errors
+    ///
+    /// Any IO Error
+    pub async fn post(
+        &self,
+        auth: Option<&BasicAuth>,
+        session_id: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<RawResponse> {
+        match self {
+            Transport::Http { client, url } => {
+                let mut rq = client.post(url).header(CONTENT_TYPE, "application/json");
+                if let Some(auth) = auth {
+                    rq = rq.basic_auth(&auth.user, Some(&auth.password));
+                }
+                if let Some(id) = session_id {
+                    rq = rq.header(SESSION_ID_HEADER, id);
+                }
+                let rsp = rq.body(body).send().await?;
+                Ok(RawResponse {
+                    status: rsp.status(),
+                    session_id: header_value(rsp.headers()),
+                    body: rsp.bytes().await?.to_vec(),
+                })
+            }
+            #[cfg(unix)]
+            Transport::Unix { client, path } => {
+                let mut rq = hyper::Request::post(hyperlocal::Uri::new(path, UNIX_RPC_PATH))
+                    .header(CONTENT_TYPE, "application/json");
+                if let Some(auth) = auth {
+                    rq = rq.header(reqwest::header::AUTHORIZATION, basic_auth_header(auth));
+                }
+                if let Some(id) = session_id {
+                    rq = rq.header(SESSION_ID_HEADER, id);
+                }
+                let rsp = client.request(rq.body(hyper::Body::from(body))?).await?;
+                let status = rsp.status();
+                let session_id = header_value(rsp.headers());
+                let body = hyper::body::to_bytes(rsp.into_body()).await?.to_vec();
+                Ok(RawResponse {
+                    status,
+                    session_id,
+                    body,
+                })
+            }
+        }
+    }
+}
+
+fn header_value(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get(SESSION_ID_HEADER)
+        .and_then(|it| it.to_str().ok())
+        .map(String::from)
+}
+
+#[cfg(unix)]
+fn basic_auth_header(auth: &BasicAuth) -> String {
+    use base64::Engine;
+    let credentials = format!("{}:{}", auth.user, auth.password);
+    format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(credentials)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_transport_from_url() {
+        assert!(matches!(
+            Transport::new("http://localhost:9091/transmission/rpc"),
+            Transport::Http { .. }
+        ));
+        #[cfg(unix)]
+        for url in &["unix:/run/transmission.sock", "unix:///run/transmission.sock"] {
+            match Transport::new(url) {
+                Transport::Unix { path, .. } => {
+                    assert_eq!(path, std::path::PathBuf::from("/run/transmission.sock"))
+                }
+                _ => panic!("{} should use a Unix socket", url),
+            }
+        }
+    }
+}
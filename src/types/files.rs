@@ -0,0 +1,225 @@
+use super::response::Torrent;
+
+/// Download priority of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl From<i8> for Priority {
+    /// low: -1, normal: 0, high: 1
+    fn from(priority: i8) -> Self {
+        match priority {
+            p if p < 0 => Priority::Low,
+            0 => Priority::Normal,
+            _ => Priority::High,
+        }
+    }
+}
+
+/// One file of a torrent, combining `files`, `fileStats`, `priorities` and `wanted`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TorrentFile {
+    /// index of the file in the torrent, as used by `files-wanted`, `priority-high`, ...
+    pub index: i32,
+    /// path inside the torrent, split on `/`
+    pub path: Vec<String>,
+    pub length: i64,
+    pub bytes_completed: i64,
+    pub priority: Priority,
+    pub wanted: bool,
+}
+
+impl TorrentFile {
+    pub fn name(&self) -> &str {
+        self.path.last().map(|it| it.as_str()).unwrap_or("")
+    }
+
+    pub fn percent_done(&self) -> f32 {
+        if self.length == 0 {
+            1.0
+        } else {
+            self.bytes_completed as f32 / self.length as f32
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.bytes_completed >= self.length
+    }
+}
+
+impl Torrent {
+    /// Per-file view of the torrent, if it was requested with `TorrentGetField::Files`.
+    /// Priority and wanted flag are taken from `fileStats`, or else from `priorities` and `wanted`;
+    /// when none of them were requested files are normal priority and wanted.
+    pub fn torrent_files(&self) -> Option<Vec<TorrentFile>> {
+        let files = self.files.as_ref()?;
+        Some(
+            files
+                .iter()
+                .enumerate()
+                .map(|(i, file)| {
+                    let stat = self.file_stats.as_ref().and_then(|it| it.get(i));
+                    let priority = stat
+                        .map(|it| it.priority)
+                        .or_else(|| self.priorities.as_ref().and_then(|it| it.get(i).copied()))
+                        .unwrap_or(0);
+                    let wanted = stat
+                        .map(|it| it.wanted)
+                        .or_else(|| self.wanted.as_ref().and_then(|it| it.get(i)).map(|it| *it != 0))
+                        .unwrap_or(true);
+                    TorrentFile {
+                        index: i as i32,
+                        path: file.name.split('/').map(String::from).collect(),
+                        length: file.length,
+                        bytes_completed: file.bytes_completed,
+                        priority: Priority::from(priority),
+                        wanted,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Directory tree of the torrent's files, see `torrent_files`
+    pub fn file_tree(&self) -> Option<FileTree> {
+        self.torrent_files().map(|files| FileTree::build(&files))
+    }
+}
+
+/// A directory or a file of a torrent, with the sizes of everything below it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTree {
+    pub name: String,
+    /// total size of the files below
+    pub length: i64,
+    pub bytes_completed: i64,
+    /// total size of the wanted files below
+    pub wanted_length: i64,
+    /// set if this node is a file
+    pub file: Option<TorrentFile>,
+    /// directories and files, in the order they appear in the torrent
+    pub children: Vec<FileTree>,
+}
+
+impl FileTree {
+    /// Builds the tree of `files`, the root node has an empty name
+    pub fn build(files: &[TorrentFile]) -> FileTree {
+        let mut root = FileTree::directory("");
+        for file in files {
+            root.insert(&file.path, file);
+        }
+        root
+    }
+
+    fn directory(name: &str) -> FileTree {
+        FileTree {
+            name: name.to_string(),
+            length: 0,
+            bytes_completed: 0,
+            wanted_length: 0,
+            file: None,
+            children: vec![],
+        }
+    }
+
+    fn insert(&mut self, path: &[String], file: &TorrentFile) {
+        self.length += file.length;
+        self.bytes_completed += file.bytes_completed;
+        if file.wanted {
+            self.wanted_length += file.length;
+        }
+        match path {
+            [] => self.file = Some(file.clone()),
+            [name, rest @ ..] => {
+                let position = self.children.iter().position(|it| &it.name == name);
+                let child = match position {
+                    Some(i) => &mut self.children[i],
+                    None => {
+                        self.children.push(FileTree::directory(name));
+                        self.children.last_mut().unwrap()
+                    }
+                };
+                child.insert(rest, file);
+            }
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn percent_done(&self) -> f32 {
+        if self.length == 0 {
+            1.0
+        } else {
+            self.bytes_completed as f32 / self.length as f32
+        }
+    }
+
+    /// Returns the node at `path` below this one, e.g. `["Season 1", "Extras"]`
+    pub fn find(&self, path: &[&str]) -> Option<&FileTree> {
+        match path {
+            [] => Some(self),
+            [name, rest @ ..] => self
+                .children
+                .iter()
+                .find(|it| it.name == *name)?
+                .find(rest),
+        }
+    }
+
+    /// All files below this node
+    pub fn files(&self) -> Vec<&TorrentFile> {
+        let mut files: Vec<&TorrentFile> = self.file.iter().collect();
+        for child in &self.children {
+            files.extend(child.files());
+        }
+        files
+    }
+
+    /// Indices of all files below this node,
+    /// to be used for `files_wanted` or `files_unwanted` to include or exclude the subtree
+    pub fn file_indices(&self) -> Vec<i32> {
+        self.files().iter().map(|it| it.index).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_file_tree() {
+        let torrent: Torrent = serde_json::from_str(
+            r#"{
+                "files": [
+                    {"name": "Show/S01/E01.mkv", "length": 100, "bytesCompleted": 100},
+                    {"name": "Show/S01/E02.mkv", "length": 100, "bytesCompleted": 50},
+                    {"name": "Show/Extras/making-of.mkv", "length": 40, "bytesCompleted": 0}
+                ],
+                "priorities": [1, 0, -1],
+                "wanted": [1, 1, 0]
+            }"#,
+        ).unwrap();
+        let files = torrent.torrent_files().unwrap();
+        assert_eq!(files[1].name(), "E02.mkv");
+        assert_eq!(files[1].percent_done(), 0.5);
+        assert_eq!(files[0].priority, Priority::High);
+        assert_eq!(files[2].priority, Priority::Low);
+        assert!(!files[2].wanted);
+
+        let tree = torrent.file_tree().unwrap();
+        let show = tree.find(&["Show"]).unwrap();
+        assert_eq!(show.length, 240);
+        assert_eq!(show.wanted_length, 200);
+        assert_eq!(show.bytes_completed, 150);
+        let season = tree.find(&["Show", "S01"]).unwrap();
+        assert_eq!(season.children.len(), 2);
+        assert_eq!(season.file_indices(), vec![0, 1]);
+        assert!(tree.find(&["Show", "Extras", "making-of.mkv"]).unwrap().is_file());
+        assert!(tree.find(&["Show", "S02"]).is_none());
+    }
+}
@@ -1,3 +1,5 @@
+mod files;
+mod pieces;
 mod request;
 mod response;
 
@@ -9,19 +11,29 @@ pub struct BasicAuth {
     pub password: String,
 }
 
+pub use self::files::FileTree;
+pub use self::files::Priority;
+pub use self::files::TorrentFile;
+
+pub use self::pieces::FilePieces;
+pub use self::pieces::PieceBitfield;
+
 pub use self::request::ArgumentFields;
 pub use self::request::Id;
-pub(crate) use self::request::RpcRequest;
+pub use self::request::RpcRequest;
+pub use self::request::SessionSetArgs;
 pub use self::request::TorrentAction;
 pub use self::request::TorrentAddArgs;
 pub use self::request::TorrentGetField;
 pub use self::request::TorrentRenamePathArgs;
+pub use self::request::TorrentSetArgs;
 
 pub use self::response::Nothing;
 pub use self::response::RpcResponse;
 pub(crate) use self::response::RpcResponseArgument;
 pub use self::response::SessionGet;
 pub use self::response::SessionStats;
+pub use self::response::Stats;
 pub use self::response::SessionClose;
 pub use self::response::BlocklistUpdate;
 pub use self::response::PortTest;
@@ -30,3 +42,7 @@ pub use self::response::Torrent;
 pub use self::response::TorrentAdded;
 pub use self::response::Torrents;
 pub use self::response::TorrentRenamePath;
+pub use self::response::TorrentStatus;
+pub use self::response::TrackerList;
+pub use self::response::File;
+pub use self::response::FileStat;
@@ -0,0 +1,198 @@
+use std::ops::Range;
+
+use base64::Engine;
+
+use super::response::{File, Torrent};
+use super::Result;
+
+/// The pieces of a torrent we have, decoded from the base64 `pieces` field
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceBitfield {
+    bits: Vec<u8>,
+    piece_count: usize,
+}
+
+/// Which pieces a file spans and how many of them we have
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePieces {
+    /// indices of the pieces holding the file's data
+    pub pieces: Range<usize>,
+    pub completed: usize,
+}
+
+impl FilePieces {
+    pub fn total(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// All pieces of the file are available, so it can be read from start to end
+    pub fn is_complete(&self) -> bool {
+        self.completed == self.total()
+    }
+
+    pub fn percent_complete(&self) -> f32 {
+        if self.total() == 0 {
+            1.0
+        } else {
+            self.completed as f32 / self.total() as f32
+        }
+    }
+}
+
+impl PieceBitfield {
+    /// Decodes the `pieces` field, in which the first piece is the highest bit of the first byte
+    ///
+    /// # Errors
+    ///
+    /// Base64 decoding error, or an error when there are fewer bits than `piece_count`
+    pub fn from_base64(pieces: &str, piece_count: usize) -> Result<PieceBitfield> {
+        let bits = base64::engine::general_purpose::STANDARD.decode(pieces.trim())?;
+        if bits.len() * 8 < piece_count {
+            return Err(From::from(format!(
+                "Bitfield of {} bytes is too short for {} pieces",
+                bits.len(),
+                piece_count
+            )));
+        }
+        Ok(PieceBitfield { bits, piece_count })
+    }
+
+    pub fn len(&self) -> usize {
+        self.piece_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.piece_count == 0
+    }
+
+    /// Tells if we have the piece `index`
+    pub fn has(&self, index: usize) -> bool {
+        index < self.piece_count && self.bits[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+
+    /// Number of pieces we have
+    pub fn completed(&self) -> usize {
+        self.count(0..self.piece_count)
+    }
+
+    /// Number of pieces we have among `pieces`
+    pub fn count(&self, pieces: Range<usize>) -> usize {
+        pieces.filter(|it| self.has(*it)).count()
+    }
+
+    /// Contiguous ranges of pieces we have
+    pub fn ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = vec![];
+        let mut start = None;
+        for index in 0..self.piece_count {
+            match (self.has(index), start) {
+                (true, None) => start = Some(index),
+                (false, Some(first)) => {
+                    ranges.push(first..index);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(first) = start {
+            ranges.push(first..self.piece_count);
+        }
+        ranges
+    }
+
+    /// For each of `files`, the pieces it spans and how many of them we have.
+    /// `files` must be in torrent order, as returned by torrent-get.
+    pub fn files(&self, files: &[File], piece_size: i64) -> Vec<FilePieces> {
+        let piece_size = piece_size.max(1);
+        let mut offset = 0;
+        files
+            .iter()
+            .map(|file| {
+                let start = offset;
+                offset += file.length;
+                let pieces = if file.length > 0 {
+                    let first = (start / piece_size) as usize;
+                    let last = ((offset - 1) / piece_size) as usize;
+                    first..(last + 1).min(self.piece_count).max(first)
+                } else {
+                    0..0
+                };
+                FilePieces {
+                    completed: self.count(pieces.clone()),
+                    pieces,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Torrent {
+    /// Decodes `pieces`, if the torrent was requested with
+    /// `TorrentGetField::Pieces` and `TorrentGetField::Piececount`
+    ///
+    /// # Errors
+    ///
+    /// Base64 decoding error
+    pub fn piece_bitfield(&self) -> Option<Result<PieceBitfield>> {
+        match (&self.pieces, self.piece_count) {
+            (Some(pieces), Some(count)) => {
+                Some(PieceBitfield::from_base64(pieces, count.max(0) as usize))
+            }
+            _ => None,
+        }
+    }
+
+    /// Piece coverage of every file, if the torrent was requested with
+    /// `Pieces`, `Piececount`, `Piecesize` and `Files` fields
+    ///
+    /// # Errors
+    ///
+    /// Base64 decoding error
+    pub fn file_pieces(&self) -> Option<Result<Vec<FilePieces>>> {
+        let files = self.files.as_ref()?;
+        let piece_size = self.piece_size?;
+        Some(
+            self.piece_bitfield()?
+                .map(|bitfield| bitfield.files(files, piece_size)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(length: i64) -> File {
+        File {
+            length,
+            bytes_completed: 0,
+            name: String::new(),
+        }
+    }
+
+    #[test]
+    pub fn test_decode_bitfield() -> Result<()> {
+        // 1110_0101 1000_0000
+        let bitfield = PieceBitfield::from_base64("5YA=", 10)?;
+        assert_eq!(bitfield.len(), 10);
+        assert!(bitfield.has(0) && bitfield.has(2) && bitfield.has(8));
+        assert!(!bitfield.has(3) && !bitfield.has(9) && !bitfield.has(42));
+        assert_eq!(bitfield.completed(), 6);
+        assert_eq!(bitfield.ranges(), vec![0..3, 5..6, 7..9]);
+        assert!(PieceBitfield::from_base64("5YA=", 17).is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_file_pieces() -> Result<()> {
+        let bitfield = PieceBitfield::from_base64("5YA=", 10)?;
+        let files = bitfield.files(&[file(25), file(0), file(10), file(65)], 10);
+        assert_eq!(files[0], FilePieces { pieces: 0..3, completed: 3 });
+        assert!(files[0].is_complete());
+        assert_eq!(files[1].total(), 0);
+        assert_eq!(files[2], FilePieces { pieces: 2..4, completed: 1 });
+        assert_eq!(files[3], FilePieces { pieces: 3..10, completed: 3 });
+        assert!(!files[3].is_complete());
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 use enum_iterator::IntoEnumIterator;
-use serde::Serialize;
+use serde::ser::SerializeSeq;
+use serde::{Serialize, Serializer};
 
-#[derive(Serialize, Debug)]
+use super::response::TrackerList;
+
+#[derive(Serialize, Debug, Clone)]
 pub struct RpcRequest {
     method: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -9,6 +12,34 @@ pub struct RpcRequest {
 }
 
 impl RpcRequest {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The lowest rpc-version of the daemon which supports the request
+    pub fn min_rpc_version(&self) -> i32 {
+        match self.method.as_str() {
+            "torrent-reannounce" | "blocklist-update" | "port-test" => 5,
+            "torrent-set-location" => 6,
+            "session-close" => 12,
+            "torrent-start-now" => 14,
+            "torrent-rename-path" | "free-space" => 15,
+            _ => match &self.arguments {
+                Some(Args::TorrentSetArgs(set)) => set.args.min_rpc_version(),
+                Some(Args::TorrentAddArgs(add)) if add.labels.is_some() => 17,
+                _ => 1,
+            },
+        }
+    }
+
+    /// Tells if sending the request twice has the same effect as sending it once
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(
+            self.method.as_str(),
+            "torrent-add" | "torrent-rename-path" | "session-close"
+        )
+    }
+
     pub fn session_get() -> RpcRequest {
         RpcRequest {
             method: String::from("session-get"),
@@ -16,6 +47,13 @@ impl RpcRequest {
         }
     }
 
+    pub fn session_set(args: SessionSetArgs) -> RpcRequest {
+        RpcRequest {
+            method: String::from("session-set"),
+            arguments: Some(Args::SessionSetArgs(args)),
+        }
+    }
+
     pub fn session_stats() -> RpcRequest {
         RpcRequest {
             method: String::from("session-stats"),
@@ -79,7 +117,7 @@ impl RpcRequest {
     pub fn torrent_add(add: TorrentAddArgs) -> RpcRequest {
         RpcRequest {
             method: String::from("torrent-add"),
-            arguments: Some(Args::TorrentAddArgs(add)),
+            arguments: Some(Args::TorrentAddArgs(Box::new(add))),
         }
     }
 
@@ -105,6 +143,13 @@ impl RpcRequest {
         }
     }
 
+    pub fn torrent_set(args: TorrentSetArgs, ids: Option<Vec<Id>>) -> RpcRequest {
+        RpcRequest {
+            method: String::from("torrent-set"),
+            arguments: Some(Args::TorrentSetArgs(TorrentSetRequestArgs { ids, args })),
+        }
+    }
+
     pub fn torrent_rename_path(ids: Vec<Id>, path: String, name: String) -> RpcRequest {
         RpcRequest {
             method: String::from("torrent-rename-path"),
@@ -121,14 +166,52 @@ impl ArgumentFields for TorrentGetField {}
 
 #[derive(Serialize, Debug, Clone)]
 #[serde(untagged)]
+#[allow(clippy::enum_variant_names)]
 pub enum Args {
+    SessionSetArgs(SessionSetArgs),
     FreeSpaceArgs(FreeSpaceArgs),
     TorrentGetArgs(TorrentGetArgs),
     TorrentActionArgs(TorrentActionArgs),
     TorrentRemoveArgs(TorrentRemoveArgs),
-    TorrentAddArgs(TorrentAddArgs),
+    TorrentAddArgs(Box<TorrentAddArgs>),
     TorrentSetLocationArgs(TorrentSetLocationArgs),
     TorrentRenamePathArgs(TorrentRenamePathArgs),
+    TorrentSetArgs(TorrentSetRequestArgs),
+}
+
+/// Session properties to change with a session-set call, fields left to None are not changed
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SessionSetArgs {
+    /// alternative download speed limit, in KBps
+    #[serde(skip_serializing_if = "Option::is_none", rename = "alt-speed-down")]
+    pub alt_speed_down: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "alt-speed-enabled")]
+    pub alt_speed_enabled: Option<bool>,
+    /// alternative upload speed limit, in KBps
+    #[serde(skip_serializing_if = "Option::is_none", rename = "alt-speed-up")]
+    pub alt_speed_up: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "blocklist-enabled")]
+    pub blocklist_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "blocklist-url")]
+    pub blocklist_url: Option<String>,
+    /// default download dir of added torrents
+    #[serde(skip_serializing_if = "Option::is_none", rename = "download-dir")]
+    pub download_dir: Option<String>,
+    /// "required", "preferred" or "tolerated"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "peer-port")]
+    pub peer_port: Option<i64>,
+    /// download speed limit, in KBps
+    #[serde(skip_serializing_if = "Option::is_none", rename = "speed-limit-down")]
+    pub speed_limit_down: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "speed-limit-down-enabled")]
+    pub speed_limit_down_enabled: Option<bool>,
+    /// upload speed limit, in KBps
+    #[serde(skip_serializing_if = "Option::is_none", rename = "speed-limit-up")]
+    pub speed_limit_up: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "speed-limit-up-enabled")]
+    pub speed_limit_up_enabled: Option<bool>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -183,13 +266,77 @@ pub struct TorrentRenamePathArgs {
 }
 
 #[derive(Serialize, Debug, Clone)]
+pub struct TorrentSetRequestArgs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ids: Option<Vec<Id>>,
+    #[serde(flatten)]
+    args: TorrentSetArgs,
+}
+
+/// Torrent properties to change with a torrent-set call, fields left to None are not changed
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct TorrentSetArgs {
+    /// replaces all labels of the torrents, rpc-version 16+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    /// announce URLs to add, each one in a new tier
+    #[serde(skip_serializing_if = "Option::is_none", rename = "trackerAdd")]
+    pub tracker_add: Option<Vec<String>>,
+    /// ids of the trackers to remove
+    #[serde(skip_serializing_if = "Option::is_none", rename = "trackerRemove")]
+    pub tracker_remove: Option<Vec<i64>>,
+    /// pairs of tracker id and its new announce URL
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        rename = "trackerReplace",
+        serialize_with = "serialize_tracker_replace"
+    )]
+    pub tracker_replace: Option<Vec<(i64, String)>>,
+    /// replaces all trackers of the torrents, rpc-version 17+
+    #[serde(skip_serializing_if = "Option::is_none", rename = "trackerList")]
+    pub tracker_list: Option<TrackerList>,
+}
+
+impl TorrentSetArgs {
+    /// The lowest rpc-version of the daemon which knows all the set fields
+    pub fn min_rpc_version(&self) -> i32 {
+        if self.tracker_list.is_some() {
+            17
+        } else if self.labels.is_some() {
+            16
+        } else if self.tracker_add.is_some()
+            || self.tracker_remove.is_some()
+            || self.tracker_replace.is_some()
+        {
+            10
+        } else {
+            1
+        }
+    }
+}
+
+/// `trackerReplace` is a flat list alternating tracker ids and URLs
+fn serialize_tracker_replace<S: Serializer>(
+    replace: &Option<Vec<(i64, String)>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let replace = replace.as_deref().unwrap_or_default();
+    let mut seq = serializer.serialize_seq(Some(replace.len() * 2))?;
+    for (id, url) in replace {
+        seq.serialize_element(id)?;
+        seq.serialize_element(url)?;
+    }
+    seq.end()
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum Id {
     Id(i64),
     Hash(String),
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, Default)]
 pub struct TorrentAddArgs {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cookies: Option<String>,
@@ -226,28 +373,12 @@ pub struct TorrentAddArgs {
     /// list of indices of files to be downloaded with normal priority
     #[serde(skip_serializing_if = "Option::is_none", rename = "priority-normal")]
     pub priority_normal: Option<Vec<i32>>,
+    /// labels of the added torrent, rpc-version 17+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
 }
 
-impl Default for TorrentAddArgs {
-    fn default() -> Self {
-        TorrentAddArgs {
-            cookies: None,
-            download_dir: None,
-            filename: None,
-            metainfo: None,
-            paused: None,
-            peer_limit: None,
-            bandwidth_priority: None,
-            files_wanted: None,
-            files_unwanted: None,
-            priority_high: None,
-            priority_low: None,
-            priority_normal: None,
-        }
-    }
-}
-
-#[derive(Clone, IntoEnumIterator)]
+#[derive(Clone, Debug, PartialEq, IntoEnumIterator)]
 pub enum TorrentGetField {
     Activitydate,
     Addeddate,
@@ -331,6 +462,35 @@ impl TorrentGetField {
     pub fn all() -> Vec<TorrentGetField> {
         TorrentGetField::into_enum_iter().collect()
     }
+
+    /// All fields known to a daemon with the given rpc-version
+    pub fn all_for(rpc_version: i32) -> Vec<TorrentGetField> {
+        TorrentGetField::into_enum_iter()
+            .filter(|it| it.is_supported(rpc_version))
+            .collect()
+    }
+
+    /// The lowest rpc-version of the daemon which knows the field
+    pub fn min_rpc_version(&self) -> i32 {
+        match self {
+            TorrentGetField::Isstalled | TorrentGetField::Queueposition => 14,
+            TorrentGetField::Etaidle
+            | TorrentGetField::Secondsdownloading
+            | TorrentGetField::Secondsseeding => 15,
+            TorrentGetField::Editdate | TorrentGetField::Labels => 16,
+            TorrentGetField::Availability
+            | TorrentGetField::Filecount
+            | TorrentGetField::Group
+            | TorrentGetField::Percentcomplete
+            | TorrentGetField::Primarymimetype
+            | TorrentGetField::Trackerlist => 17,
+            _ => 1,
+        }
+    }
+
+    pub fn is_supported(&self, rpc_version: i32) -> bool {
+        self.min_rpc_version() <= rpc_version
+    }
 }
 
 impl TorrentGetField {
@@ -386,7 +546,7 @@ impl TorrentGetField {
             TorrentGetField::Piececount => "pieceCount",
             TorrentGetField::Piecesize => "pieceSize",
             TorrentGetField::Priorities => "priorities",
-            TorrentGetField::Primarymimetype => "primaryMimeType",
+            TorrentGetField::Primarymimetype => "primary-mime-type",
             TorrentGetField::Queueposition => "queuePosition",
             TorrentGetField::Ratedownload => "rateDownload",
             TorrentGetField::Rateupload => "rateUpload",
@@ -417,6 +577,7 @@ impl TorrentGetField {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum TorrentAction {
     Start,
     Stop,
@@ -1,4 +1,5 @@
-use serde::Deserialize;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 #[derive(Deserialize, Debug)]
 pub struct RpcResponse<T: RpcResponseArgument> {
     pub arguments: T,
@@ -84,8 +85,9 @@ pub struct Torrent {
     pub activity_date: Option<i64>,
     #[serde(rename = "addedDate")]
     pub added_date: Option<i64>,
-    // TODO: what
-    // pub availability: Option<i64>,
+    /// for each piece, the number of connected peers having it, or -1 if we have it ourselves
+    #[serde(default, deserialize_with = "lenient")]
+    pub availability: Option<Vec<i64>>,
     #[serde(rename = "bandwidthPriority")]
     pub bandwidth_priority: Option<i64>,
     pub comment: Option<String>,
@@ -115,13 +117,14 @@ pub struct Torrent {
     pub eta: Option<i64>,
     #[serde(rename = "etaIdle")]
     pub eta_idle: Option<i64>,
-    #[serde(rename = "fileCount")]
+    #[serde(rename = "file-count")]
     pub file_count: Option<i64>,
     pub files: Option<Vec<File>>,
     #[serde(rename = "fileStats")]
     pub file_stats: Option<Vec<FileStat>>,
-    // TODO: returns 0
-    // pub group: Option<String>,
+    /// name of the bandwidth group
+    #[serde(default, deserialize_with = "lenient")]
+    pub group: Option<String>,
     #[serde(rename = "hashString")]
     pub hash_string: Option<String>,
     #[serde(rename = "haveUnchecked")]
@@ -137,8 +140,8 @@ pub struct Torrent {
     pub is_private: Option<bool>,
     #[serde(rename = "isStalled")]
     pub is_stalled: Option<bool>,
-    // TODO: returns 0
-    // pub labels: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub labels: Option<Vec<String>>,
     #[serde(rename = "leftUntilDone")]
     pub left_until_done: Option<i64>,
     #[serde(rename = "magnetLink")]
@@ -150,7 +153,7 @@ pub struct Torrent {
     #[serde(rename = "metadataPercentComplete")]
     pub metadata_percent_complete: Option<f32>,
     pub name: Option<String>,
-    #[serde(rename = "peerLimit")]
+    #[serde(rename = "peer-limit")]
     pub peer_limit: Option<i64>,
     pub peers: Option<Vec<Peer>>,
     #[serde(rename = "peersConnected")]
@@ -165,9 +168,12 @@ pub struct Torrent {
     pub percent_complete: Option<f32>,
     #[serde(rename = "percentDone")]
     pub percent_done: Option<f32>,
+    /// base64 encoded bitfield of the pieces we have, see `Torrent::piece_bitfield`
     pub pieces: Option<String>,
     #[serde(rename = "pieceCount")]
     pub piece_count: Option<i64>,
+    #[serde(rename = "pieceSize")]
+    pub piece_size: Option<i64>,
     /// for each file in files, their download priority (low:-1,normal:0,high:1)
     pub priorities: Option<Vec<i8>>,
     #[serde(rename = "primary-mime-type")]
@@ -198,9 +204,8 @@ pub struct Torrent {
     pub start_date: Option<i64>,
     pub status: Option<i64>,
     pub trackers: Option<Vec<Trackers>>,
-    // TODO: returns 0
-    // #[serde(rename = "trackerList")]
-    // pub tracker_list: Option<String>,
+    #[serde(rename = "trackerList", default, deserialize_with = "lenient")]
+    pub tracker_list: Option<TrackerList>,
     #[serde(rename = "trackerStats")]
     pub tracker_stats: Option<Vec<TrackerStats>>,
     #[serde(rename = "totalSize")]
@@ -222,6 +227,153 @@ pub struct Torrent {
     pub webseeds_sending_to_us: Option<i64>,
 }
 
+/// Announce URLs of a torrent grouped in tiers
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(from = "String")]
+pub struct TrackerList {
+    pub tiers: Vec<Vec<String>>,
+}
+
+impl From<String> for TrackerList {
+    /// Parses the `trackerList` format: one announce URL per line, tiers separated by a blank line
+    fn from(list: String) -> Self {
+        let mut tiers = vec![];
+        let mut tier: Vec<String> = vec![];
+        for line in list.lines().map(str::trim) {
+            if line.is_empty() {
+                if !tier.is_empty() {
+                    tiers.push(std::mem::take(&mut tier));
+                }
+            } else {
+                tier.push(line.to_string());
+            }
+        }
+        if !tier.is_empty() {
+            tiers.push(tier);
+        }
+        TrackerList { tiers }
+    }
+}
+
+impl TrackerList {
+    /// Adds announce URLs to the tier `tier` (starting from 0),
+    /// or to a new last tier if `tier` is None or does not exist yet.
+    /// URLs which are already in the list are skipped.
+    pub fn add(&mut self, tier: Option<usize>, urls: &[String]) {
+        let urls: Vec<String> = urls.iter().filter(|url| !self.contains(url)).cloned().collect();
+        if urls.is_empty() {
+            return;
+        }
+        match tier.and_then(|it| self.tiers.get_mut(it)) {
+            Some(tier) => tier.extend(urls),
+            None => self.tiers.push(urls),
+        }
+    }
+
+    /// Removes an announce URL, dropping its tier if it becomes empty
+    pub fn remove(&mut self, url: &str) {
+        for tier in &mut self.tiers {
+            tier.retain(|it| it != url);
+        }
+        self.tiers.retain(|tier| !tier.is_empty());
+    }
+
+    /// Replaces an announce URL, keeping its tier
+    pub fn replace(&mut self, old_url: &str, new_url: &str) {
+        if self.contains(new_url) {
+            self.remove(old_url);
+            return;
+        }
+        for url in self.tiers.iter_mut().flatten() {
+            if url == old_url {
+                *url = new_url.to_string();
+            }
+        }
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.tiers.iter().flatten().any(|it| it == url)
+    }
+}
+
+impl Serialize for TrackerList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl std::fmt::Display for TrackerList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tiers: Vec<String> = self.tiers.iter().map(|tier| tier.join("\n")).collect();
+        write!(f, "{}", tiers.join("\n\n"))
+    }
+}
+
+/// Deserializes a field as `None` when the daemon sends it in an unexpected shape,
+/// e.g. older daemons answer 0 for fields they don't know
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Lenient<T> {
+        Expected(T),
+        Unexpected(IgnoredAny),
+    }
+    Ok(match Lenient::deserialize(deserializer)? {
+        Lenient::Expected(value) => Some(value),
+        Lenient::Unexpected(_) => None,
+    })
+}
+
+/// Activity of a torrent, as found in `Torrent.status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TorrentStatus {
+    Stopped,
+    QueuedToVerify,
+    Verifying,
+    QueuedToDownload,
+    Downloading,
+    QueuedToSeed,
+    Seeding,
+}
+
+impl TorrentStatus {
+    pub fn from_i64(status: i64) -> Option<TorrentStatus> {
+        match status {
+            0 => Some(TorrentStatus::Stopped),
+            1 => Some(TorrentStatus::QueuedToVerify),
+            2 => Some(TorrentStatus::Verifying),
+            3 => Some(TorrentStatus::QueuedToDownload),
+            4 => Some(TorrentStatus::Downloading),
+            5 => Some(TorrentStatus::QueuedToSeed),
+            6 => Some(TorrentStatus::Seeding),
+            _ => None,
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            TorrentStatus::Stopped => "stopped",
+            TorrentStatus::QueuedToVerify => "queued to verify",
+            TorrentStatus::Verifying => "verifying",
+            TorrentStatus::QueuedToDownload => "queued to download",
+            TorrentStatus::Downloading => "downloading",
+            TorrentStatus::QueuedToSeed => "queued to seed",
+            TorrentStatus::Seeding => "seeding",
+        }
+    }
+}
+
+impl Torrent {
+    /// Decodes `status`, if the torrent was requested with `TorrentGetField::Status`
+    pub fn torrent_status(&self) -> Option<TorrentStatus> {
+        self.status.and_then(TorrentStatus::from_i64)
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Stats {
     #[serde(rename = "filesAdded")]
@@ -246,6 +398,7 @@ impl RpcResponseArgument for Torrents<Torrent> {}
 pub struct Trackers {
     pub id: i32,
     pub announce: String,
+    pub tier: Option<i32>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -374,6 +527,9 @@ impl RpcResponseArgument for Nothing {}
 pub struct TorrentAdded {
     #[serde(rename = "torrent-added")]
     pub torrent_added: Option<Torrent>,
+    /// the torrent which was already present, set instead of `torrent_added`
+    #[serde(rename = "torrent-duplicate")]
+    pub torrent_duplicate: Option<Torrent>,
 }
 impl RpcResponseArgument for TorrentAdded {}
 
@@ -384,3 +540,68 @@ pub struct TorrentRenamePath {
     pub id: Option<i64>,
 }
 impl RpcResponseArgument for TorrentRenamePath {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_torrent_new_fields() {
+        let torrent: Torrent = serde_json::from_str(
+            r#"{
+                "availability": [-1, 2, 0],
+                "group": "slow",
+                "labels": ["tv", "hd"],
+                "trackerList": "https://a/announce\nhttps://b/announce\n\nudp://c:80\n"
+            }"#,
+        ).unwrap();
+        assert_eq!(torrent.availability, Some(vec![-1, 2, 0]));
+        assert_eq!(torrent.group.as_deref(), Some("slow"));
+        assert_eq!(torrent.labels, Some(vec!["tv".to_string(), "hd".to_string()]));
+        let tracker_list = torrent.tracker_list.unwrap();
+        assert_eq!(
+            tracker_list.tiers,
+            vec![
+                vec!["https://a/announce".to_string(), "https://b/announce".to_string()],
+                vec!["udp://c:80".to_string()],
+            ]
+        );
+        assert_eq!(
+            tracker_list.to_string(),
+            "https://a/announce\nhttps://b/announce\n\nudp://c:80"
+        );
+    }
+
+    #[test]
+    pub fn test_tracker_list_edit() {
+        let mut list = TrackerList::from(String::from("a\nb\n\nc"));
+        list.add(Some(1), &["d".to_string(), "a".to_string()]);
+        list.add(None, &["e".to_string()]);
+        assert_eq!(list.to_string(), "a\nb\n\nc\nd\n\ne");
+        list.remove("e");
+        list.replace("b", "f");
+        list.replace("c", "d");
+        assert_eq!(list.to_string(), "a\nf\n\nd");
+    }
+
+    #[test]
+    pub fn test_torrent_new_fields_from_old_daemon() {
+        let torrent: Torrent = serde_json::from_str(
+            r#"{"availability": 0, "group": 0, "labels": 0, "trackerList": 0, "id": 1}"#,
+        ).unwrap();
+        assert_eq!(torrent.availability, None);
+        assert_eq!(torrent.group, None);
+        assert_eq!(torrent.labels, None);
+        assert_eq!(torrent.tracker_list, None);
+        assert_eq!(torrent.id, Some(1));
+    }
+
+    #[test]
+    pub fn test_torrent_duplicate() {
+        let added: TorrentAdded = serde_json::from_str(
+            r#"{"torrent-duplicate": {"hashString": "abc", "id": 3, "name": "file"}}"#,
+        ).unwrap();
+        assert!(added.torrent_added.is_none());
+        assert_eq!(added.torrent_duplicate.and_then(|it| it.hash_string).as_deref(), Some("abc"));
+    }
+}
@@ -0,0 +1,285 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::types::{Id, Result, Torrent, TorrentAction, TorrentGetField, TorrentStatus};
+use crate::TransClient;
+
+/// How `verify_and_wait` waits for the verifications
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    pub poll_interval: Duration,
+    /// torrents not verified by then are reported as timed out
+    pub timeout: Option<Duration>,
+    /// stops waiting once set to true, e.g. from another task.
+    /// The daemon goes on verifying, stopping the torrents aborts it.
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> VerifyOptions {
+        VerifyOptions {
+            poll_interval: Duration::from_secs(1),
+            timeout: None,
+            cancel: None,
+        }
+    }
+}
+
+impl VerifyOptions {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|it| it.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyState {
+    /// check-wait, queued behind other verifications
+    Queued,
+    /// check, with the recheck progress from 0 to 1
+    Checking(f32),
+    Verified,
+    /// the torrent was removed while verifying
+    Removed,
+    TimedOut,
+    Cancelled,
+}
+
+/// Where the verification of a torrent stands, and what it found
+#[derive(Debug, Clone, PartialEq)]
+pub struct TorrentVerification {
+    pub id: Id,
+    pub name: String,
+    pub state: VerifyState,
+    pub have_valid_before: i64,
+    pub have_valid: i64,
+    pub corrupt_ever_before: i64,
+    pub corrupt_ever: i64,
+    pub percent_done_before: f32,
+    pub percent_done: f32,
+    /// error of the torrent once verified, e.g. missing data
+    pub error: Option<String>,
+}
+
+impl TorrentVerification {
+    /// Returns the verification of `torrent`, from its state before verifying
+    fn new(torrent: &Torrent) -> Option<TorrentVerification> {
+        let id = match (&torrent.hash_string, torrent.id) {
+            (Some(hash), _) => Id::Hash(hash.to_lowercase()),
+            (None, Some(id)) => Id::Id(id),
+            _ => return None,
+        };
+        let have_valid = torrent.have_valid.unwrap_or(0);
+        let corrupt_ever = torrent.corrupt_ever.unwrap_or(0);
+        let percent_done = torrent.percent_done.unwrap_or(0.0);
+        Some(TorrentVerification {
+            id,
+            name: torrent.name.clone().unwrap_or_default(),
+            state: VerifyState::Queued,
+            have_valid_before: have_valid,
+            have_valid,
+            corrupt_ever_before: corrupt_ever,
+            corrupt_ever,
+            percent_done_before: percent_done,
+            percent_done,
+            error: None,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !matches!(self.state, VerifyState::Queued | VerifyState::Checking(_))
+    }
+
+    /// Growth of corruptEver while verifying. corruptEver only counts corrupt pieces downloaded
+    /// from peers, a recheck finding damaged data on disk leaves it unchanged, see `data_lost`.
+    pub fn corrupt_found(&self) -> i64 {
+        self.corrupt_ever - self.corrupt_ever_before
+    }
+
+    /// Bytes no longer valid after the verification, e.g. missing or damaged data
+    pub fn valid_lost(&self) -> i64 {
+        (self.have_valid_before - self.have_valid).max(0)
+    }
+
+    /// Whether the verification found data missing or damaged: valid bytes were lost
+    /// or the percent done dropped
+    pub fn data_lost(&self) -> bool {
+        self.valid_lost() > 0 || self.percent_done < self.percent_done_before
+    }
+
+    /// Updates the verification from the torrent's last poll, a torrent neither
+    /// queued nor checking is verified
+    fn update(&mut self, torrent: &Torrent) {
+        self.have_valid = torrent.have_valid.unwrap_or(self.have_valid);
+        self.corrupt_ever = torrent.corrupt_ever.unwrap_or(self.corrupt_ever);
+        self.percent_done = torrent.percent_done.unwrap_or(self.percent_done);
+        self.state = match torrent.status.and_then(TorrentStatus::from_i64) {
+            Some(TorrentStatus::QueuedToVerify) => VerifyState::Queued,
+            Some(TorrentStatus::Verifying) => VerifyState::Checking(torrent.recheck_progress.unwrap_or(0.0)),
+            _ => VerifyState::Verified,
+        };
+        if self.state == VerifyState::Verified {
+            self.error = torrent
+                .error_string
+                .clone()
+                .filter(|it| !it.is_empty() && torrent.error.unwrap_or(0) != 0);
+        }
+    }
+
+    fn matches(&self, torrent: &Torrent) -> bool {
+        match &self.id {
+            Id::Hash(hash) => torrent.hash_string.as_ref().is_some_and(|it| it.eq_ignore_ascii_case(hash)),
+            Id::Id(id) => torrent.id == Some(*id),
+        }
+    }
+}
+
+/// Verifies the torrents and waits until the daemon is done, calling `on_progress`
+/// with the state of each torrent after every poll.
+/// Returns what the verification found: valid bytes before and after, whether data was lost,
+/// and percent done.
+///
+/// # Errors
+///
+/// Any IO Error or Deserialization error, or the daemon refusing to verify
+///
+/// # Example
+///
+/// ```
+/// extern crate transmission_rpc;
+///
+/// use std::env;
+/// use std::time::Duration;
+/// use dotenv::dotenv;
+/// use transmission_rpc::TransClient;
+/// use transmission_rpc::types::{Id, Result, BasicAuth};
+/// use transmission_rpc::verify::{verify_and_wait, VerifyOptions};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     dotenv().ok();
+///     env_logger::init();
+///     let url= env::var("TURL")?;
+///     let basic_auth = BasicAuth{user: env::var("TUSER")?, password: env::var("TPWD")?};
+///     let mut client = TransClient::with_auth(&url, basic_auth);
+///     let options = VerifyOptions {
+///         timeout: Some(Duration::from_secs(3600)),
+///         ..VerifyOptions::default()
+///     };
+///     let report = verify_and_wait(&mut client, vec![Id::Id(1)], options, |progress| println!("{:?}", progress)).await?;
+///     for torrent in report {
+///         println!("{}: {:?}, {} bytes lost, {}% done", torrent.name, torrent.state, torrent.valid_lost(), torrent.percent_done * 100.0);
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub async fn verify_and_wait<F: FnMut(&[TorrentVerification])>(
+    client: &mut TransClient,
+    ids: Vec<Id>,
+    options: VerifyOptions,
+    mut on_progress: F,
+) -> Result<Vec<TorrentVerification>> {
+    let fields = vec![
+        TorrentGetField::Id,
+        TorrentGetField::HashString,
+        TorrentGetField::Name,
+        TorrentGetField::Status,
+        TorrentGetField::Recheckprogress,
+        TorrentGetField::Havevalid,
+        TorrentGetField::Corruptever,
+        TorrentGetField::Percentdone,
+        TorrentGetField::Error,
+        TorrentGetField::Errorstring,
+    ];
+    let before = client.torrent_get(Some(fields.clone()), Some(ids)).await?;
+    let mut verifications: Vec<TorrentVerification> = before
+        .arguments
+        .torrents
+        .iter()
+        .filter_map(TorrentVerification::new)
+        .collect();
+    if verifications.is_empty() {
+        return Ok(verifications);
+    }
+    let ids: Vec<Id> = verifications.iter().map(|it| it.id.clone()).collect();
+    let response = client.torrent_action(TorrentAction::Verify, ids).await?;
+    if !response.is_ok() {
+        return Err(From::from(format!("torrent-verify failed: {}", response.result)));
+    }
+    let started = Instant::now();
+    loop {
+        tokio::time::sleep(options.poll_interval).await;
+        let ids: Vec<Id> = verifications
+            .iter()
+            .filter(|it| !it.is_finished())
+            .map(|it| it.id.clone())
+            .collect();
+        let torrents = client.torrent_get(Some(fields.clone()), Some(ids)).await?.arguments.torrents;
+        for verification in verifications.iter_mut().filter(|it| !it.is_finished()) {
+            match torrents.iter().find(|it| verification.matches(it)) {
+                Some(torrent) => verification.update(torrent),
+                None => verification.state = VerifyState::Removed,
+            }
+        }
+        let stop = if options.is_cancelled() {
+            Some(VerifyState::Cancelled)
+        } else if options.timeout.is_some_and(|it| started.elapsed() > it) {
+            Some(VerifyState::TimedOut)
+        } else {
+            None
+        };
+        if let Some(state) = stop {
+            for verification in verifications.iter_mut().filter(|it| !it.is_finished()) {
+                verification.state = state.clone();
+            }
+        }
+        on_progress(&verifications);
+        if verifications.iter().all(TorrentVerification::is_finished) {
+            return Ok(verifications);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent(json: &str) -> Torrent {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    pub fn test_verification() {
+        let before = torrent(
+            r#"{"id": 1, "hashString": "AB", "name": "Disk", "status": 6, "haveValid": 1000,
+                "corruptEver": 10, "percentDone": 1.0}"#,
+        );
+        let mut verification = TorrentVerification::new(&before).unwrap();
+        assert_eq!(verification.id, Id::Hash("ab".to_string()));
+        assert!(verification.matches(&before));
+        assert!(!verification.is_finished());
+
+        verification.update(&torrent(r#"{"hashString": "ab", "status": 1}"#));
+        assert_eq!(verification.state, VerifyState::Queued);
+        verification.update(&torrent(r#"{"hashString": "ab", "status": 2, "recheckProgress": 0.5}"#));
+        assert_eq!(verification.state, VerifyState::Checking(0.5));
+
+        verification.update(&torrent(
+            r#"{"hashString": "ab", "status": 0, "haveValid": 600, "corruptEver": 10,
+                "percentDone": 0.6, "error": 3, "errorString": "No data found!"}"#,
+        ));
+        assert!(verification.is_finished());
+        assert_eq!(verification.state, VerifyState::Verified);
+        assert_eq!(verification.corrupt_found(), 0);
+        assert_eq!(verification.valid_lost(), 400);
+        assert!(verification.data_lost());
+        assert_eq!(verification.percent_done, 0.6);
+        assert_eq!(verification.error.as_deref(), Some("No data found!"));
+
+        let mut intact = TorrentVerification::new(&before).unwrap();
+        intact.update(&torrent(r#"{"hashString": "ab", "status": 6, "haveValid": 1000, "percentDone": 1.0}"#));
+        assert_eq!(intact.state, VerifyState::Verified);
+        assert!(!intact.data_lost());
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use base64::Engine;
+
+use crate::types::{Result, TorrentAddArgs};
+use crate::TransClient;
+
+/// Adds the `.torrent` and `.magnet` files dropped in a local folder to the daemon,
+/// which does not need to see the folder.
+///
+/// A file in a subfolder, e.g. `tv/hd/show.torrent`, is added with the download dir
+/// mapped to `tv/hd` (or else to `tv`), and with the labels `tv` and `hd` if
+/// `subfolder_labels` is set. Added files are moved to `done_dir`, files the daemon
+/// refused to `failed_dir`, keeping their subfolder.
+///
+/// ```
+/// use std::time::Duration;
+/// use transmission_rpc::watch::WatchFolder;
+///
+/// let mut watch = WatchFolder::new("/srv/watch");
+/// watch.download_dirs.insert("tv".to_string(), "/data/tv".to_string());
+/// watch.download_dirs.insert("movies".to_string(), "/data/movies".to_string());
+/// watch.subfolder_labels = true;
+/// ```
+#[derive(Debug, Clone)]
+pub struct WatchFolder {
+    pub dir: PathBuf,
+    pub done_dir: PathBuf,
+    pub failed_dir: PathBuf,
+    /// download dir by subfolder path, with `/` separators
+    pub download_dirs: HashMap<String, String>,
+    /// download dir of subfolders without a mapping: the subfolder path is appended to it.
+    /// Torrents of unmapped subfolders go to the daemon's default dir if None.
+    pub download_root: Option<String>,
+    /// label torrents with the names of their subfolders, rpc-version 17+
+    pub subfolder_labels: bool,
+    /// add torrents without starting them
+    pub paused: bool,
+    /// files modified more recently are left for the next scan, they may still be written
+    pub settle_time: Duration,
+}
+
+/// A file of the watch folder and how it is added
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub path: PathBuf,
+    /// subfolder components between the watch folder and the file
+    pub subfolder: Vec<String>,
+    pub download_dir: Option<String>,
+    pub labels: Vec<String>,
+    /// name of the added torrent, or the reason it was refused
+    pub outcome: std::result::Result<Option<String>, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub added: Vec<Import>,
+    pub failed: Vec<Import>,
+}
+
+impl WatchFolder {
+    /// Watches `dir`, moving files to its `.done` and `.failed` subfolders
+    pub fn new<P: AsRef<Path>>(dir: P) -> WatchFolder {
+        let dir = dir.as_ref().to_path_buf();
+        WatchFolder {
+            done_dir: dir.join(".done"),
+            failed_dir: dir.join(".failed"),
+            dir,
+            download_dirs: HashMap::new(),
+            download_root: None,
+            subfolder_labels: false,
+            paused: false,
+            settle_time: Duration::from_secs(2),
+        }
+    }
+
+    /// Adds all settled files of the folder, then moves them to the done or failed folder.
+    /// A file is left in place when the daemon cannot be reached, to be added by the next scan.
+    ///
+    /// # Errors
+    ///
+    /// IO Error reading the folder or moving a file, or any error calling the daemon
+    pub async fn scan(&self, client: &mut TransClient) -> Result<ImportReport> {
+        let mut report = ImportReport::default();
+        let mut rpc_version = None;
+        for mut import in self.pending(SystemTime::now())? {
+            let mut add = match read_source(&import.path) {
+                Ok(add) => add,
+                Err(e) => {
+                    import.outcome = Err(e.to_string());
+                    self.finish(import, &mut report)?;
+                    continue;
+                }
+            };
+            add.download_dir = import.download_dir.clone();
+            add.paused = if self.paused { Some(true) } else { None };
+            if !import.labels.is_empty() {
+                let version = match rpc_version {
+                    Some(version) => version,
+                    None => *rpc_version.insert(client.rpc_version().await?),
+                };
+                if version >= 17 {
+                    add.labels = Some(import.labels.clone());
+                } else {
+                    warn!("Labels of {} ignored, they need rpc-version 17", import.path.display());
+                }
+            }
+            let response = client.torrent_add(add).await?;
+            import.outcome = if response.is_ok() {
+                Ok(response.arguments.torrent_added.and_then(|it| it.name))
+            } else {
+                Err(response.result)
+            };
+            self.finish(import, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    /// Scans the folder every `interval` and logs the files added or failed
+    ///
+    /// # Errors
+    ///
+    /// The error of the first scan that fails, e.g. reading the folder or calling the daemon
+    pub async fn watch(&self, client: &mut TransClient, interval: Duration) -> Result<()> {
+        loop {
+            let report = self.scan(client).await?;
+            for import in &report.added {
+                info!("Added {}", import.path.display());
+            }
+            for import in &report.failed {
+                warn!(
+                    "Unable to add {}: {}",
+                    import.path.display(),
+                    import.outcome.as_ref().err().map(String::as_str).unwrap_or("")
+                );
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Settled torrent and magnet files of the folder, sorted by path
+    fn pending(&self, now: SystemTime) -> Result<Vec<Import>> {
+        let mut files = vec![];
+        self.collect(&self.dir, &mut files)?;
+        files.sort();
+        let mut imports = vec![];
+        for path in files {
+            let modified = fs::metadata(&path)?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() < self.settle_time {
+                continue;
+            }
+            imports.push(self.import(path));
+        }
+        Ok(imports)
+    }
+
+    fn collect(&self, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|it| it.to_string_lossy().starts_with('.'));
+            if hidden || path == self.done_dir || path == self.failed_dir {
+                continue;
+            }
+            if path.is_dir() {
+                self.collect(&path, files)?;
+            } else if matches!(extension(&path).as_deref(), Some("torrent") | Some("magnet")) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// How the file at `path` is added, from the subfolder it is in
+    fn import(&self, path: PathBuf) -> Import {
+        let subfolder: Vec<String> = path
+            .parent()
+            .and_then(|it| it.strip_prefix(&self.dir).ok())
+            .map(|it| it.iter().map(|c| c.to_string_lossy().into_owned()).collect())
+            .unwrap_or_default();
+        let download_dir = (1..=subfolder.len())
+            .rev()
+            .find_map(|n| self.download_dirs.get(&subfolder[..n].join("/")).cloned())
+            .or_else(|| {
+                let root = self.download_root.as_ref()?;
+                if subfolder.is_empty() {
+                    return None;
+                }
+                Some(format!("{}/{}", root.trim_end_matches('/'), subfolder.join("/")))
+            });
+        let labels = if self.subfolder_labels { subfolder.clone() } else { vec![] };
+        Import {
+            path,
+            subfolder,
+            download_dir,
+            labels,
+            outcome: Ok(None),
+        }
+    }
+
+    /// Moves the file to the done or failed folder and records it in the report
+    fn finish(&self, mut import: Import, report: &mut ImportReport) -> Result<()> {
+        let target_dir = if import.outcome.is_ok() { &self.done_dir } else { &self.failed_dir };
+        let target_dir = import.subfolder.iter().fold(target_dir.clone(), |dir, it| dir.join(it));
+        fs::create_dir_all(&target_dir)?;
+        let name = import.path.file_name().map(|it| it.to_os_string()).unwrap_or_default();
+        let mut target = target_dir.join(&name);
+        let mut n = 1;
+        while target.exists() {
+            target = target_dir.join(format!("{}.{}", name.to_string_lossy(), n));
+            n += 1;
+        }
+        move_file(&import.path, &target)?;
+        import.path = target;
+        if import.outcome.is_ok() {
+            report.added.push(import);
+        } else {
+            report.failed.push(import);
+        }
+        Ok(())
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|it| it.to_string_lossy().to_lowercase())
+}
+
+/// Add arguments for a file: the metainfo of a `.torrent`,
+/// the first magnet link or URL of a `.magnet`
+fn read_source(path: &Path) -> Result<TorrentAddArgs> {
+    if extension(path).as_deref() == Some("torrent") {
+        let content = fs::read(path)?;
+        if content.first() != Some(&b'd') {
+            return Err(From::from("Not a torrent file"));
+        }
+        return Ok(TorrentAddArgs {
+            metainfo: Some(base64::engine::general_purpose::STANDARD.encode(content)),
+            ..TorrentAddArgs::default()
+        });
+    }
+    let content = fs::read_to_string(path)?;
+    let link = content
+        .lines()
+        .map(str::trim)
+        .find(|it| it.starts_with("magnet:") || it.starts_with("http://") || it.starts_with("https://"))
+        .ok_or("No magnet link or URL found")?;
+    Ok(TorrentAddArgs {
+        filename: Some(link.to_string()),
+        ..TorrentAddArgs::default()
+    })
+}
+
+/// Renames, or copies then removes when the target is on another file system
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_subfolder_mapping() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("watch-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("tv/hd"))?;
+        fs::create_dir_all(dir.join("books"))?;
+        fs::create_dir_all(dir.join(".done"))?;
+        fs::write(dir.join("tv/hd/show.torrent"), "d4:infod4:name4:showee")?;
+        fs::write(dir.join("books/novel.magnet"), "\n  magnet:?xt=urn:btih:abc\n")?;
+        fs::write(dir.join("root.TORRENT"), "not bencoded")?;
+        fs::write(dir.join("notes.txt"), "")?;
+        fs::write(dir.join(".done/old.torrent"), "")?;
+
+        let mut watch = WatchFolder::new(&dir);
+        watch.download_dirs.insert("tv".to_string(), "/data/tv".to_string());
+        watch.download_root = Some("/data/".to_string());
+        watch.subfolder_labels = true;
+        watch.settle_time = Duration::from_secs(0);
+
+        let imports = watch.pending(SystemTime::now() + Duration::from_secs(1))?;
+        let found: Vec<(&[String], Option<&str>)> = imports
+            .iter()
+            .map(|it| (it.subfolder.as_slice(), it.download_dir.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (&["books".to_string()][..], Some("/data/books")),
+                (&[][..], None),
+                (&["tv".to_string(), "hd".to_string()][..], Some("/data/tv")),
+            ]
+        );
+        assert_eq!(imports[2].labels, vec!["tv", "hd"]);
+
+        let magnet = read_source(&imports[0].path)?;
+        assert_eq!(magnet.filename.as_deref(), Some("magnet:?xt=urn:btih:abc"));
+        assert!(read_source(&imports[1].path).is_err());
+        assert!(read_source(&imports[2].path)?.metainfo.is_some());
+
+        // a file still being written is left for the next scan
+        watch.settle_time = Duration::from_secs(3600);
+        assert!(watch.pending(SystemTime::now())?.is_empty());
+
+        let mut report = ImportReport::default();
+        let mut failed = imports[1].clone();
+        failed.outcome = Err("Not a torrent file".to_string());
+        watch.finish(failed, &mut report)?;
+        let mut added = imports[2].clone();
+        added.outcome = Ok(Some("show".to_string()));
+        watch.finish(added, &mut report)?;
+        assert!(dir.join(".failed/root.TORRENT").exists());
+        assert!(dir.join(".done/tv/hd/show.torrent").exists());
+        assert!(!dir.join("tv/hd/show.torrent").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}